@@ -249,10 +249,6 @@ fn monitor_communication(comm_port: &str, baud: u32) {
         match comm.read(&mut buffer) {
             Ok(n) if n > 0 => {
                 total += n;
-                let now = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_millis();
                 println!(
                     "[{:6.3}s] Received {} bytes: {:02X?}",
                     start.elapsed().as_secs_f32(),
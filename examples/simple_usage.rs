@@ -1,7 +1,6 @@
 /// 简单使用示例
 ///
 /// 这个例子展示了如何使用 Prelude Power Controller 的基本功能
-
 use prelude_power_controller::{DeviceSide, PowerController, WireMode};
 use std::io::{Read, Write};
 use std::time::Duration;
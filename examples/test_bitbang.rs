@@ -10,17 +10,14 @@
 ///   VCHARGER2 = 0x08  (bit 3)
 ///   POW1      = 0x10  (bit 4)
 ///   POW2      = 0x20  (bit 5)
-use libftd2xx::{BitMode, DeviceType, Ftdi, FtdiCommon};
+use libftd2xx::{BitMode, Ftdi, FtdiCommon};
 use std::thread::sleep;
 use std::time::Duration;
 
-// Hardware constants from PreludeSettings.h
+// Hardware constants from PreludeSettings.h (only Device 1 is exercised here)
 const RESET1: u8 = 0x01;
-const RESET2: u8 = 0x02;
 const VCHARGER1: u8 = 0x04;
-const VCHARGER2: u8 = 0x08;
 const POW1: u8 = 0x10;
-const POW2: u8 = 0x20;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // ---------------------------------------------------------------
@@ -114,7 +114,7 @@ fn test_communication(comm_port: &str, baud: u32) -> Result<usize, Box<dyn std::
 
     // 测试2: 发送数据并等待响应
     println!("\n  📤 Test 2: Send and receive...");
-    let test_commands = vec![
+    let test_commands = [
         b"AT\r\n".to_vec(),
         b"HELLO\n".to_vec(),
         b"\r\n".to_vec(),
@@ -152,7 +152,7 @@ fn test_power_sequence(controller: &mut PowerController, side: DeviceSide, name:
     }
 }
 
-fn test_communication(controller: &mut PowerController, comm_port: &str, name: &str) {
+fn test_communication(_controller: &mut PowerController, comm_port: &str, name: &str) {
     println!("📡 Testing {} Communication ({})", name, comm_port);
     println!("─────────────────────────────────────");
 
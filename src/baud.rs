@@ -0,0 +1,337 @@
+use crate::device_info::DeviceInfo;
+use crate::dut::{DutClient, DutOptions};
+use crate::error::{PowerControllerError, Result};
+use crate::power::WireMode;
+use crate::transport::{PinTransport, SerialTransport};
+use std::cmp::Ordering;
+use std::time::Duration;
+
+/// Rates tried by `scan_dut2_baud.rs`, most likely first.
+pub const DEFAULT_BAUD_CANDIDATES: &[u32] = &[
+    9600, 115200, 19200, 38400, 57600, 14400, 28800, 4800, 2400, 1200, 230400, 460800, 921600,
+];
+
+/// How one baud rate fared.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BaudCandidate {
+    pub baud: u32,
+    /// Bytes the DUT sent in reply, without the echo of the probe.
+    pub bytes: usize,
+    /// Share of printable ASCII (including CR, LF, TAB) in the reply.
+    pub printable_ratio: f32,
+    /// Bytes typical of a baud mismatch: 0x00 (break) and anything above
+    /// 0x7F. The OS does not report UART framing errors, so they are
+    /// estimated from these.
+    pub framing_errors: usize,
+    /// The reply parsed as `Key:Value` lines with at least one known field.
+    pub structured: bool,
+    /// 0.0 (nothing usable) to 1.0 (clean, structured reply).
+    pub score: f32,
+    /// Start of the reply, lossily decoded.
+    pub sample: String,
+    /// Why the port could not be probed at this rate.
+    pub error: Option<String>,
+}
+
+impl BaudCandidate {
+    fn failed(baud: u32, error: String) -> Self {
+        Self {
+            baud,
+            bytes: 0,
+            printable_ratio: 0.0,
+            framing_errors: 0,
+            structured: false,
+            score: 0.0,
+            sample: String::new(),
+            error: Some(error),
+        }
+    }
+
+    fn from_reply(baud: u32, raw: &[u8]) -> Self {
+        let printable = raw
+            .iter()
+            .filter(|b| matches!(b, 0x20..=0x7E | b'\r' | b'\n' | b'\t'))
+            .count();
+        let framing_errors = raw.iter().filter(|b| **b == 0x00 || **b > 0x7F).count();
+        let text = String::from_utf8_lossy(raw);
+        let structured = DeviceInfo::parse(&text)
+            .map(|info| {
+                let known = DeviceInfo {
+                    extra: Default::default(),
+                    ..info
+                };
+                known != DeviceInfo::default()
+            })
+            .unwrap_or(false);
+
+        let (printable_ratio, clean_ratio) = if raw.is_empty() {
+            (0.0, 0.0)
+        } else {
+            let len = raw.len() as f32;
+            (printable as f32 / len, 1.0 - framing_errors as f32 / len)
+        };
+        let score = printable_ratio * 0.6 + clean_ratio * 0.2 + if structured { 0.2 } else { 0.0 };
+
+        Self {
+            baud,
+            bytes: raw.len(),
+            printable_ratio,
+            framing_errors,
+            structured,
+            score,
+            sample: text.chars().take(64).collect(),
+            error: None,
+        }
+    }
+}
+
+/// Candidates ranked best first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BaudDetection {
+    pub ranked: Vec<BaudCandidate>,
+    /// Score lead of the best candidate over the runner-up, 0.0 to 1.0.
+    /// Low when nothing answered or two rates look equally plausible.
+    pub confidence: f32,
+}
+
+impl BaudDetection {
+    /// Best candidate, if any rate produced a usable reply.
+    pub fn best(&self) -> Option<&BaudCandidate> {
+        self.ranked.first().filter(|c| c.score > 0.0)
+    }
+
+    pub fn baud(&self) -> Option<u32> {
+        self.best().map(|c| c.baud)
+    }
+}
+
+/// Opens `port` at every candidate rate, sends `probe_cmd` (e.g.
+/// `b"[init_status,]"`) and ranks the rates by how the reply looks.
+///
+/// On a single-wire link the probe is read back cleanly at any rate, so
+/// `mode` decides whether that echo is dropped; only the DUT's reply is
+/// scored.
+pub fn detect_baud(
+    port: &str,
+    mode: WireMode,
+    candidates: &[u32],
+    probe_cmd: &[u8],
+) -> Result<BaudDetection> {
+    let options = DutOptions {
+        response_timeout: Duration::from_secs(1),
+        ..DutOptions::for_mode(mode)
+    };
+    detect_baud_with(
+        |baud| Ok(Box::new(SerialTransport::open(port, baud)?)),
+        candidates,
+        probe_cmd,
+        &options,
+    )
+}
+
+/// Same as `detect_baud` on transports opened by `open` for each rate;
+/// `options` decides how long each probe listens and whether the echo is
+/// stripped. A reply that still starts with the probe has it removed.
+pub fn detect_baud_with<F>(
+    mut open: F,
+    candidates: &[u32],
+    probe_cmd: &[u8],
+    options: &DutOptions,
+) -> Result<BaudDetection>
+where
+    F: FnMut(u32) -> Result<Box<dyn PinTransport>>,
+{
+    let mut ranked = Vec::with_capacity(candidates.len());
+
+    for &baud in candidates {
+        let candidate = match probe(open(baud), probe_cmd, options) {
+            Ok(raw) => BaudCandidate::from_reply(baud, &raw),
+            Err(e) => BaudCandidate::failed(baud, e.to_string()),
+        };
+        ranked.push(candidate);
+    }
+
+    // Stable sort keeps the caller's preference order between equal scores
+    ranked.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+
+    let best = ranked.first().map_or(0.0, |c| c.score);
+    let runner_up = ranked.get(1).map_or(0.0, |c| c.score);
+
+    Ok(BaudDetection {
+        ranked,
+        confidence: (best - runner_up).clamp(0.0, 1.0),
+    })
+}
+
+/// Reply to the probe; silence is an empty reply, not an error.
+fn probe(
+    port: Result<Box<dyn PinTransport>>,
+    probe_cmd: &[u8],
+    options: &DutOptions,
+) -> Result<Vec<u8>> {
+    let mut client = DutClient::with_transport(port?, options.clone())?;
+    client.clear_input()?;
+    client.write_raw(probe_cmd)?;
+
+    let raw = match client.receive() {
+        Ok(response) => response.raw,
+        Err(PowerControllerError::Timeout) => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    // Echo read back on a link the options do not mark as single-wire
+    match raw.strip_prefix(probe_cmd) {
+        Some(reply) if !probe_cmd.is_empty() => Ok(reply.to_vec()),
+        _ => Ok(raw),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::io::{self, Read, Write};
+
+    const PROBE: &[u8] = b"[init_status,]";
+    const REPLY: &[u8] = b"Aw:Init\r\nBat:T\r\nCalib:230\r\n";
+
+    /// UART that answers the probe with `reply`, reading back the writes
+    /// first if `echo` is set.
+    struct Scripted {
+        reply: Vec<u8>,
+        echo: bool,
+        rx: VecDeque<u8>,
+    }
+
+    impl Read for Scripted {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.rx.is_empty() {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "timed out"));
+            }
+            let n = buf.len().min(self.rx.len());
+            for (slot, byte) in buf.iter_mut().zip(self.rx.drain(..n)) {
+                *slot = byte;
+            }
+            Ok(n)
+        }
+    }
+
+    impl Write for Scripted {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.echo {
+                self.rx.extend(buf);
+            }
+            self.rx.extend(&self.reply);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl PinTransport for Scripted {
+        fn set_timeout(&mut self, _: Duration) -> Result<()> {
+            Ok(())
+        }
+
+        fn name(&self) -> &str {
+            "scripted"
+        }
+    }
+
+    /// A DUT at 115200 baud: other rates read garbage.
+    fn dut(echo: bool) -> impl FnMut(u32) -> Result<Box<dyn PinTransport>> {
+        move |baud| {
+            let reply = match baud {
+                115200 => REPLY.to_vec(),
+                _ => vec![0x00, 0xF8, 0x80, 0xFE, 0x00, 0xE0],
+            };
+            Ok(Box::new(Scripted {
+                reply,
+                echo,
+                rx: VecDeque::new(),
+            }))
+        }
+    }
+
+    fn options(mode: WireMode) -> DutOptions {
+        DutOptions {
+            response_timeout: Duration::from_millis(30),
+            idle_gap: Duration::from_millis(10),
+            poll_interval: Duration::from_millis(1),
+            ..DutOptions::for_mode(mode)
+        }
+    }
+
+    #[test]
+    fn right_rate_wins() {
+        let detection = detect_baud_with(
+            dut(false),
+            &[9600, 115200, 19200],
+            PROBE,
+            &options(WireMode::DoubleWire),
+        )
+        .unwrap();
+
+        let best = detection.best().unwrap();
+        assert_eq!(best.baud, 115200);
+        assert!(best.structured);
+        assert_eq!(best.bytes, REPLY.len());
+        assert!(detection.confidence > 0.5);
+        assert!(detection.ranked[1].framing_errors > 0);
+    }
+
+    #[test]
+    fn silent_port_has_no_best_rate() {
+        let silent = |_| -> Result<Box<dyn PinTransport>> {
+            Ok(Box::new(Scripted {
+                reply: Vec::new(),
+                echo: false,
+                rx: VecDeque::new(),
+            }))
+        };
+        let detection = detect_baud_with(
+            silent,
+            &[9600, 115200],
+            PROBE,
+            &options(WireMode::DoubleWire),
+        )
+        .unwrap();
+
+        assert!(detection.best().is_none());
+        assert_eq!(detection.baud(), None);
+        assert_eq!(detection.confidence, 0.0);
+        assert!(detection
+            .ranked
+            .iter()
+            .all(|c| c.bytes == 0 && c.error.is_none()));
+    }
+
+    #[test]
+    fn echo_of_the_probe_is_not_scored() {
+        for mode in [WireMode::SingleWire, WireMode::DoubleWire] {
+            let detection =
+                detect_baud_with(dut(true), &[9600, 115200, 19200], PROBE, &options(mode)).unwrap();
+
+            assert_eq!(detection.baud(), Some(115200), "{mode:?}");
+            assert_eq!(detection.ranked[0].bytes, REPLY.len());
+            assert!(detection.ranked[1..]
+                .iter()
+                .all(|c| c.printable_ratio == 0.0));
+            assert!(detection.confidence > 0.5);
+        }
+    }
+
+    #[test]
+    fn failed_open_is_reported() {
+        let detection = detect_baud_with(
+            |_| Err(PowerControllerError::Timeout),
+            &[9600],
+            PROBE,
+            &options(WireMode::SingleWire),
+        )
+        .unwrap();
+        assert!(detection.best().is_none());
+        assert!(detection.ranked[0].error.is_some());
+    }
+}
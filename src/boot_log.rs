@@ -0,0 +1,319 @@
+use crate::error::{PowerControllerError, Result};
+use crate::fixture::Fixture;
+use crate::pins::PinState;
+use crate::power::{DeviceSide, PowerController};
+use crate::transport::PinTransport;
+use std::io::ErrorKind;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Bytes returned by one read of a DUT UART.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogChunk {
+    /// Time since the POW edge; for `BootLog::before_pow`, time until it.
+    pub at: Duration,
+    pub data: Vec<u8>,
+}
+
+/// One line of boot output, without CR/LF.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootLine {
+    /// Arrival of the first byte of the line, since the POW edge.
+    pub at: Duration,
+    pub text: String,
+}
+
+/// Silence between two chunks of the same DUT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogGap {
+    /// Arrival of the chunk before the silence, since the POW edge.
+    pub at: Duration,
+    pub length: Duration,
+}
+
+/// Everything one DUT sent after its POW edge, plus what it sent on
+/// VCHARGER alone before that.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootLog {
+    pub side: DeviceSide,
+    /// Time from the VCHARGER edge to the POW edge (`vcharger_lead` plus
+    /// the write time).
+    pub vcharger_lead: Duration,
+    /// Output between the VCHARGER and the POW edge, timed back from the
+    /// POW edge.
+    pub before_pow: Vec<LogChunk>,
+    pub chunks: Vec<LogChunk>,
+    /// Non-empty lines of `chunks`; a last line without newline is
+    /// included.
+    pub lines: Vec<BootLine>,
+    /// Time from the POW edge to the first byte, `None` if the DUT stayed
+    /// silent after it.
+    pub first_byte: Option<Duration>,
+    /// Bytes in `chunks`.
+    pub total_bytes: usize,
+    /// Silences of at least `BootLogCapture::gap_threshold`.
+    pub gaps: Vec<LogGap>,
+    /// Why the UART stopped being read before the capture ended.
+    pub error: Option<String>,
+}
+
+impl BootLog {
+    fn new(
+        side: DeviceSide,
+        vcharger_lead: Duration,
+        before_pow: Vec<LogChunk>,
+        chunks: Vec<LogChunk>,
+        gap_threshold: Duration,
+    ) -> Self {
+        let mut lines = Vec::new();
+        let mut line: Option<(Duration, Vec<u8>)> = None;
+        for chunk in &chunks {
+            for &byte in &chunk.data {
+                let (_, text) = line.get_or_insert_with(|| (chunk.at, Vec::new()));
+                if byte == b'\n' {
+                    push_line(&mut lines, line.take());
+                } else {
+                    text.push(byte);
+                }
+            }
+        }
+        push_line(&mut lines, line);
+
+        let gaps = chunks
+            .windows(2)
+            .filter_map(|pair| {
+                let length = pair[1].at.saturating_sub(pair[0].at);
+                (length >= gap_threshold).then_some(LogGap {
+                    at: pair[0].at,
+                    length,
+                })
+            })
+            .collect();
+
+        Self {
+            side,
+            vcharger_lead,
+            before_pow,
+            first_byte: chunks.first().map(|c| c.at),
+            total_bytes: chunks.iter().map(|c| c.data.len()).sum(),
+            lines,
+            gaps,
+            chunks,
+            error: None,
+        }
+    }
+
+    /// All received bytes in order, `before_pow` included.
+    pub fn raw(&self) -> Vec<u8> {
+        self.before_pow
+            .iter()
+            .chain(&self.chunks)
+            .flat_map(|c| c.data.iter().copied())
+            .collect()
+    }
+
+    /// `raw` decoded as UTF-8, invalid sequences replaced.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.raw()).into_owned()
+    }
+}
+
+fn push_line(lines: &mut Vec<BootLine>, line: Option<(Duration, Vec<u8>)>) {
+    if let Some((at, text)) = line {
+        let text = String::from_utf8_lossy(&text);
+        let text = text.trim_end_matches('\r');
+        if !text.is_empty() {
+            lines.push(BootLine {
+                at,
+                text: text.to_string(),
+            });
+        }
+    }
+}
+
+/// Cold-boots DUTs while listening on their UARTs, replacing the "power
+/// up, open C/D at 9600, dump for N seconds" loops of the capture examples.
+///
+/// The supplies of every captured side are switched off for `off_time`,
+/// the listeners start, VCHARGER is raised `vcharger_lead` before POW, and
+/// the UARTs are read for `listen` after the POW edge. Bytes read before
+/// the VCHARGER edge are residue of the previous boot and are dropped. The
+/// DUTs are left powered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BootLogCapture {
+    pub listen: Duration,
+    pub off_time: Duration,
+    pub vcharger_lead: Duration,
+    /// Silence reported as a gap.
+    pub gap_threshold: Duration,
+    /// Read timeout of each listener poll.
+    pub poll_interval: Duration,
+}
+
+impl Default for BootLogCapture {
+    fn default() -> Self {
+        Self {
+            listen: Duration::from_secs(30),
+            off_time: Duration::from_secs(2),
+            vcharger_lead: Duration::from_millis(100),
+            gap_threshold: Duration::from_millis(100),
+            poll_interval: Duration::from_millis(10),
+        }
+    }
+}
+
+impl BootLogCapture {
+    /// Captures the boot of `side` (or both DUTs) on the fixture's DUT
+    /// UARTs, opened at the baud rate of the controller's wire mode.
+    pub fn capture_fixture(
+        &self,
+        fixture: &Fixture,
+        controller: &mut PowerController,
+        side: DeviceSide,
+    ) -> Result<Vec<BootLog>> {
+        let baud = controller.wire_mode().baud_rate();
+        let mut ports: Vec<(DeviceSide, Box<dyn PinTransport>)> = Vec::new();
+        for side in sides(side) {
+            ports.push((side, fixture.open_dut_transport(side, baud)?));
+        }
+        self.capture(controller, ports)
+    }
+
+    /// Captures the boot of the DUT behind each port, one `BootLog` per
+    /// port in the same order. Each port must belong to a single DUT.
+    pub fn capture(
+        &self,
+        controller: &mut PowerController,
+        ports: Vec<(DeviceSide, Box<dyn PinTransport>)>,
+    ) -> Result<Vec<BootLog>> {
+        if ports.iter().any(|(side, _)| *side == DeviceSide::Both) {
+            return Err(PowerControllerError::InvalidDeviceSide);
+        }
+        let supplies = ports.iter().fold(PinState::empty(), |pins, (side, _)| {
+            pins | PinState::supply(*side)
+        });
+        let pow = ports.iter().fold(PinState::empty(), |pins, (side, _)| {
+            pins | PinState::pow(*side)
+        });
+
+        controller.apply(|pins| pins.remove(supplies))?;
+        std::thread::sleep(self.off_time);
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let listeners: Vec<(DeviceSide, JoinHandle<Listened>)> = ports
+            .into_iter()
+            .map(|(side, port)| {
+                let stop = stop.clone();
+                let poll = self.poll_interval;
+                (side, std::thread::spawn(move || listen(port, poll, stop)))
+            })
+            .collect();
+
+        let edges = self.power_up(controller, supplies, pow);
+        if edges.is_ok() {
+            std::thread::sleep(self.listen);
+        }
+        stop.store(true, Ordering::Relaxed);
+
+        let listened: Vec<_> = listeners
+            .into_iter()
+            .map(|(side, thread)| (side, thread.join()))
+            .collect();
+        let (supply_edge, pow_edge) = edges?;
+
+        Ok(listened
+            .into_iter()
+            .map(|(side, result)| {
+                let Listened { chunks, error } = result.unwrap_or_else(|_| Listened {
+                    chunks: Vec::new(),
+                    error: Some("listener panicked".to_string()),
+                });
+                let mut before_pow = Vec::new();
+                let mut after_pow = Vec::new();
+                for (at, data) in chunks {
+                    if at >= pow_edge {
+                        after_pow.push(LogChunk {
+                            at: at - pow_edge,
+                            data,
+                        });
+                    } else if at >= supply_edge {
+                        before_pow.push(LogChunk {
+                            at: pow_edge - at,
+                            data,
+                        });
+                    }
+                }
+                BootLog {
+                    error,
+                    ..BootLog::new(
+                        side,
+                        pow_edge - supply_edge,
+                        before_pow,
+                        after_pow,
+                        self.gap_threshold,
+                    )
+                }
+            })
+            .collect())
+    }
+
+    /// Raises VCHARGER, then POW, and returns the times of both edges.
+    /// Each edge is taken before its write, so output triggered by the
+    /// write cannot precede it.
+    fn power_up(
+        &self,
+        controller: &mut PowerController,
+        supplies: PinState,
+        pow: PinState,
+    ) -> Result<(Instant, Instant)> {
+        let supply_edge = Instant::now();
+        controller.apply(|pins| pins.insert(supplies - pow))?;
+        std::thread::sleep(self.vcharger_lead);
+        let pow_edge = Instant::now();
+        controller.apply(|pins| pins.insert(pow))?;
+        Ok((supply_edge, pow_edge))
+    }
+}
+
+pub(crate) fn sides(side: DeviceSide) -> Vec<DeviceSide> {
+    match side {
+        DeviceSide::Both => vec![DeviceSide::Device1, DeviceSide::Device2],
+        side => vec![side],
+    }
+}
+
+struct Listened {
+    chunks: Vec<(Instant, Vec<u8>)>,
+    error: Option<String>,
+}
+
+fn listen(mut port: Box<dyn PinTransport>, poll: Duration, stop: Arc<AtomicBool>) -> Listened {
+    let mut chunks = Vec::new();
+    if let Err(e) = port.set_timeout(poll) {
+        return Listened {
+            chunks,
+            error: Some(e.to_string()),
+        };
+    }
+
+    let mut buffer = [0u8; 1024];
+    while !stop.load(Ordering::Relaxed) {
+        match port.read(&mut buffer) {
+            Ok(n) if n > 0 => chunks.push((Instant::now(), buffer[..n].to_vec())),
+            Ok(_) => {}
+            Err(e) if e.kind() == ErrorKind::TimedOut => {}
+            Err(e) => {
+                return Listened {
+                    chunks,
+                    error: Some(e.to_string()),
+                }
+            }
+        }
+    }
+    Listened {
+        chunks,
+        error: None,
+    }
+}
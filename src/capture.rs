@@ -0,0 +1,664 @@
+//! Session capture: every pin-state write and every byte exchanged with the
+//! DUT UARTs, with monotonic timestamps, as newline-delimited JSON.
+//!
+//! The first line is a header, `{"format":"prelude-capture","version":1}`;
+//! each following line is one record such as
+//! `{"t_us":1520,"kind":"tx","dut":2,"data":"5b696e69745f7374617475732c5d"}`
+//! with `t_us` counted from the start of the recording and `data` in hex.
+//! [`Recorder`] writes captures by wrapping the transports of a session;
+//! [`Capture`] reads them back and, with the `sim` feature, replays them on
+//! a `SimulatedFixture`.
+use crate::error::{PowerControllerError, Result};
+use crate::power::DeviceSide;
+use crate::transport::PinTransport;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+const FORMAT: &str = "prelude-capture";
+const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    format: String,
+    version: u32,
+}
+
+/// What happened at one point of a session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CaptureEvent {
+    /// State byte written to the power-control interface.
+    Pins { state: u8 },
+    /// Bytes sent to a DUT UART.
+    Tx {
+        #[serde(with = "dut_number")]
+        dut: DeviceSide,
+        #[serde(with = "hex")]
+        data: Vec<u8>,
+    },
+    /// Bytes received from a DUT UART.
+    Rx {
+        #[serde(with = "dut_number")]
+        dut: DeviceSide,
+        #[serde(with = "hex")]
+        data: Vec<u8>,
+    },
+}
+
+/// One line of a capture.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureRecord {
+    /// Time since the start of the recording.
+    #[serde(rename = "t_us", with = "micros")]
+    pub at: Duration,
+    #[serde(flatten)]
+    pub event: CaptureEvent,
+}
+
+struct RecorderState {
+    writer: Box<dyn Write + Send>,
+    started: Instant,
+}
+
+/// Writes a capture. Cheap to clone; all clones append to the same file.
+#[derive(Clone)]
+pub struct Recorder {
+    state: Arc<Mutex<RecorderState>>,
+}
+
+impl Recorder {
+    /// Creates (or truncates) the capture file at `path`.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+
+    /// Records into `writer`, starting the clock now.
+    pub fn new(writer: impl Write + Send + 'static) -> Result<Self> {
+        let recorder = Self {
+            state: Arc::new(Mutex::new(RecorderState {
+                writer: Box::new(writer),
+                started: Instant::now(),
+            })),
+        };
+        write_line(
+            &mut recorder.lock(),
+            &Header {
+                format: FORMAT.to_string(),
+                version: VERSION,
+            },
+        )?;
+        Ok(recorder)
+    }
+
+    /// Appends one event, timestamped now. Each record is flushed so a
+    /// crashed session still leaves a readable capture.
+    pub fn record(&self, event: CaptureEvent) -> io::Result<()> {
+        let mut state = self.lock();
+        let at = state.started.elapsed();
+        write_line(&mut state, &CaptureRecord { at, event })
+    }
+
+    /// Wraps the power-control transport so every state payload is recorded.
+    pub fn power_transport(&self, port: Box<dyn PinTransport>) -> Box<dyn PinTransport> {
+        Box::new(RecordingTransport {
+            port,
+            recorder: self.clone(),
+            dut: None,
+        })
+    }
+
+    /// Wraps the UART of `dut` so every byte sent and received is recorded.
+    /// `dut` must be a single device.
+    pub fn dut_transport(
+        &self,
+        dut: DeviceSide,
+        port: Box<dyn PinTransport>,
+    ) -> Result<Box<dyn PinTransport>> {
+        if dut == DeviceSide::Both {
+            return Err(PowerControllerError::InvalidDeviceSide);
+        }
+        Ok(Box::new(RecordingTransport {
+            port,
+            recorder: self.clone(),
+            dut: Some(dut),
+        }))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, RecorderState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn write_line<T: Serialize>(state: &mut RecorderState, value: &T) -> io::Result<()> {
+    // Serialized up front so a record that fails leaves no partial line
+    let mut line = serde_json::to_vec(value)?;
+    line.push(b'\n');
+    state.writer.write_all(&line)?;
+    state.writer.flush()
+}
+
+/// `PinTransport` that forwards to another one and records the traffic.
+struct RecordingTransport {
+    port: Box<dyn PinTransport>,
+    recorder: Recorder,
+    /// DUT whose UART this is, `None` for the power-control interface.
+    dut: Option<DeviceSide>,
+}
+
+impl Read for RecordingTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.port.read(buf)?;
+        if let (Some(dut), true) = (self.dut, n > 0) {
+            self.recorder.record(CaptureEvent::Rx {
+                dut,
+                data: buf[..n].to_vec(),
+            })?;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for RecordingTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.port.write(buf)?;
+        if let (Some(dut), true) = (self.dut, n > 0) {
+            self.recorder.record(CaptureEvent::Tx {
+                dut,
+                data: buf[..n].to_vec(),
+            })?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.port.flush()
+    }
+}
+
+impl PinTransport for RecordingTransport {
+    fn write_payload(&mut self, payload: &[u8]) -> Result<()> {
+        self.port.write_payload(payload)?;
+        if let (None, Some(&state)) = (self.dut, payload.last()) {
+            self.recorder.record(CaptureEvent::Pins { state })?;
+        }
+        Ok(())
+    }
+
+    fn read_pins(&mut self) -> Result<u8> {
+        self.port.read_pins()
+    }
+
+    fn set_pin_directions(&mut self, outputs: u8) -> Result<()> {
+        self.port.set_pin_directions(outputs)
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.port.set_timeout(timeout)
+    }
+
+    fn name(&self) -> &str {
+        self.port.name()
+    }
+}
+
+/// A capture read back from disk.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Capture {
+    /// Records in file order, i.e. by time.
+    pub records: Vec<CaptureRecord>,
+}
+
+impl Capture {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Parses a capture, checking the header first.
+    pub fn read(reader: impl BufRead) -> Result<Self> {
+        let mut lines = reader.lines().enumerate();
+        let parse_error = |line: usize, reason: String| PowerControllerError::CaptureFormat {
+            line: line + 1,
+            reason,
+        };
+
+        let header: Header = match lines.next() {
+            Some((i, line)) => {
+                serde_json::from_str(&line?).map_err(|e| parse_error(i, e.to_string()))?
+            }
+            None => return Err(parse_error(0, "empty capture".to_string())),
+        };
+        if header.format != FORMAT || header.version != VERSION {
+            return Err(parse_error(
+                0,
+                format!("unsupported format {} v{}", header.format, header.version),
+            ));
+        }
+
+        let mut records = Vec::new();
+        for (i, line) in lines {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            records.push(serde_json::from_str(&line).map_err(|e| parse_error(i, e.to_string()))?);
+        }
+        Ok(Self { records })
+    }
+
+    /// Pin states written, with their times.
+    pub fn pin_writes(&self) -> impl Iterator<Item = (Duration, u8)> + '_ {
+        self.records.iter().filter_map(|r| match r.event {
+            CaptureEvent::Pins { state } => Some((r.at, state)),
+            _ => None,
+        })
+    }
+
+    /// Everything sent to (`tx`) or received from one DUT, concatenated.
+    pub fn dut_bytes(&self, side: DeviceSide, tx: bool) -> Vec<u8> {
+        self.records
+            .iter()
+            .filter_map(|r| match &r.event {
+                CaptureEvent::Tx { dut, data } if tx && *dut == side => Some(data),
+                CaptureEvent::Rx { dut, data } if !tx && *dut == side => Some(data),
+                _ => None,
+            })
+            .flatten()
+            .copied()
+            .collect()
+    }
+}
+
+/// DUT sides are written as 1 and 2; `Both` cannot be written.
+mod dut_number {
+    use crate::power::DeviceSide;
+    use serde::{de, ser};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(dut: &DeviceSide, serializer: S) -> Result<S::Ok, S::Error> {
+        match dut {
+            DeviceSide::Device1 => serializer.serialize_u8(1),
+            DeviceSide::Device2 => serializer.serialize_u8(2),
+            DeviceSide::Both => Err(ser::Error::custom("a record belongs to a single DUT")),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DeviceSide, D::Error> {
+        match u8::deserialize(deserializer)? {
+            1 => Ok(DeviceSide::Device1),
+            2 => Ok(DeviceSide::Device2),
+            other => Err(de::Error::custom(format!("invalid DUT number {other}"))),
+        }
+    }
+}
+
+mod hex {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::fmt::Write;
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut text = String::with_capacity(data.len() * 2);
+        for byte in data {
+            let _ = write!(text, "{byte:02x}");
+        }
+        serializer.serialize_str(&text)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        if text.len() % 2 != 0 {
+            return Err(D::Error::custom("odd number of hex digits"));
+        }
+        // Pairs of bytes, not of chars: non-ASCII input must not split a char
+        text.as_bytes()
+            .chunks(2)
+            .map(|pair| match (digit(pair[0]), digit(pair[1])) {
+                (Some(high), Some(low)) => Ok(high << 4 | low),
+                _ => Err(D::Error::custom(format!(
+                    "invalid hex digits '{}'",
+                    String::from_utf8_lossy(pair)
+                ))),
+            })
+            .collect()
+    }
+
+    fn digit(c: u8) -> Option<u8> {
+        (c as char).to_digit(16).map(|d| d as u8)
+    }
+}
+
+mod micros {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(at: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(at.as_micros() as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_micros)
+    }
+}
+
+#[cfg(feature = "sim")]
+pub use replay::{Replay, ReplayPort};
+
+#[cfg(feature = "sim")]
+mod replay {
+    use super::{Capture, CaptureEvent};
+    use crate::error::Result;
+    use crate::power::DeviceSide;
+    use crate::sim::SimulatedFixture;
+    use crate::transport::PinTransport;
+    use std::io::{self, Read, Write};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread::JoinHandle;
+    use std::time::{Duration, Instant};
+
+    impl Capture {
+        /// Replays the capture on `fixture`, starting now: pin writes are
+        /// applied at their recorded offsets in a background thread, and
+        /// `Replay::dut_port` serves what each DUT sent.
+        pub fn replay(&self, fixture: &SimulatedFixture) -> Replay {
+            let started = Instant::now();
+            let stop = Arc::new(AtomicBool::new(false));
+            let pins: Vec<(Duration, u8)> = self.pin_writes().collect();
+            let thread = {
+                let stop = stop.clone();
+                let mut transport = fixture.transport();
+                std::thread::spawn(move || {
+                    for (at, state) in pins {
+                        while !stop.load(Ordering::Relaxed) && started.elapsed() < at {
+                            let left = at.saturating_sub(started.elapsed());
+                            std::thread::sleep(left.min(Duration::from_millis(20)));
+                        }
+                        if stop.load(Ordering::Relaxed) {
+                            return;
+                        }
+                        let mut payload = [0x55u8; 7];
+                        payload[6] = state;
+                        let _ = transport.write_payload(&payload);
+                    }
+                })
+            };
+
+            Replay {
+                capture: Arc::new(self.clone()),
+                started,
+                stop,
+                thread: Some(thread),
+            }
+        }
+    }
+
+    /// A capture being replayed; stops replaying pins when dropped.
+    pub struct Replay {
+        capture: Arc<Capture>,
+        started: Instant,
+        stop: Arc<AtomicBool>,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl Replay {
+        /// UART of `side` as it behaved in the capture.
+        pub fn dut_port(&self, side: DeviceSide) -> ReplayPort {
+            ReplayPort {
+                capture: self.capture.clone(),
+                side,
+                started: self.started,
+                next: 0,
+                offset: 0,
+                written: Vec::new(),
+                timeout: Duration::from_millis(1000),
+            }
+        }
+
+        /// Waits until every pin write has been applied.
+        pub fn wait(mut self) {
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    impl Drop for Replay {
+        fn drop(&mut self) {
+            self.stop.store(true, Ordering::Relaxed);
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    /// DUT UART of a [`Replay`]: reads return the bytes the DUT sent, each
+    /// once its recorded time has passed. Writes are kept for comparison
+    /// with the capture but do not influence what is read.
+    pub struct ReplayPort {
+        capture: Arc<Capture>,
+        side: DeviceSide,
+        started: Instant,
+        /// Record holding the next unread byte, and the position in it.
+        next: usize,
+        offset: usize,
+        written: Vec<u8>,
+        timeout: Duration,
+    }
+
+    impl ReplayPort {
+        /// Bytes written to this port so far.
+        pub fn written(&self) -> &[u8] {
+            &self.written
+        }
+
+        fn read_due(&mut self, buf: &mut [u8]) -> usize {
+            let elapsed = self.started.elapsed();
+            let mut n = 0;
+            while n < buf.len() {
+                let Some(record) = self.capture.records.get(self.next) else {
+                    break;
+                };
+                let data = match &record.event {
+                    CaptureEvent::Rx { dut, data } if *dut == self.side => data,
+                    _ => {
+                        self.next += 1;
+                        continue;
+                    }
+                };
+                if record.at > elapsed {
+                    break;
+                }
+
+                let take = (data.len() - self.offset).min(buf.len() - n);
+                buf[n..n + take].copy_from_slice(&data[self.offset..self.offset + take]);
+                n += take;
+                self.offset += take;
+                if self.offset == data.len() {
+                    self.next += 1;
+                    self.offset = 0;
+                }
+            }
+            n
+        }
+    }
+
+    impl Read for ReplayPort {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if buf.is_empty() {
+                return Ok(0);
+            }
+
+            let deadline = Instant::now() + self.timeout;
+            loop {
+                let n = self.read_due(buf);
+                if n > 0 {
+                    return Ok(n);
+                }
+                if Instant::now() >= deadline {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "Operation timed out",
+                    ));
+                }
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+    }
+
+    impl Write for ReplayPort {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl PinTransport for ReplayPort {
+        fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+            self.timeout = timeout;
+            Ok(())
+        }
+
+        fn name(&self) -> &str {
+            "replay"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// In-memory capture file shared with the recorder.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    fn line_of(result: Result<Capture>) -> usize {
+        match result {
+            Err(PowerControllerError::CaptureFormat { line, .. }) => line,
+            other => panic!("expected a format error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn round_trips_through_the_recorder() {
+        let buffer = Buffer::default();
+        let recorder = Recorder::new(buffer.clone()).unwrap();
+        let events = [
+            CaptureEvent::Pins { state: 0xDC },
+            CaptureEvent::Tx {
+                dut: DeviceSide::Device2,
+                data: b"[init_status,]".to_vec(),
+            },
+            CaptureEvent::Rx {
+                dut: DeviceSide::Device1,
+                data: vec![0x00, 0xFF, b'\n'],
+            },
+        ];
+        for event in &events {
+            recorder.record(event.clone()).unwrap();
+        }
+
+        let text = buffer.text();
+        assert!(text.starts_with("{\"format\":\"prelude-capture\",\"version\":1}\n"));
+        assert!(
+            text.contains("\"kind\":\"tx\",\"dut\":2,\"data\":\"5b696e69745f7374617475732c5d\"")
+        );
+
+        let capture = Capture::read(text.as_bytes()).unwrap();
+        let read: Vec<_> = capture.records.iter().map(|r| r.event.clone()).collect();
+        assert_eq!(read, events);
+        assert!(capture.records.windows(2).all(|w| w[0].at <= w[1].at));
+        assert_eq!(
+            capture.pin_writes().map(|(_, s)| s).collect::<Vec<_>>(),
+            [0xDC]
+        );
+        assert_eq!(
+            capture.dut_bytes(DeviceSide::Device2, true),
+            b"[init_status,]"
+        );
+        assert_eq!(
+            capture.dut_bytes(DeviceSide::Device1, false),
+            [0x00, 0xFF, b'\n']
+        );
+    }
+
+    #[test]
+    fn records_belong_to_a_single_dut() {
+        let buffer = Buffer::default();
+        let recorder = Recorder::new(buffer.clone()).unwrap();
+        let written = buffer.text();
+
+        let both = CaptureEvent::Tx {
+            dut: DeviceSide::Both,
+            data: vec![0],
+        };
+        assert!(recorder.record(both).is_err());
+        assert_eq!(buffer.text(), written);
+        assert!(Capture::read(written.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn keeps_microsecond_timestamps() {
+        let text = "{\"format\":\"prelude-capture\",\"version\":1}\n\
+            {\"t_us\":1520,\"kind\":\"pins\",\"state\":192}\n\n";
+        let capture = Capture::read(text.as_bytes()).unwrap();
+        assert_eq!(capture.records.len(), 1);
+        assert_eq!(capture.records[0].at, Duration::from_micros(1520));
+    }
+
+    #[test]
+    fn rejects_a_missing_or_foreign_header() {
+        assert_eq!(line_of(Capture::read(&b""[..])), 1);
+        assert_eq!(line_of(Capture::read(&b"not json\n"[..])), 1);
+        let other = "{\"format\":\"prelude-capture\",\"version\":2}\n";
+        assert_eq!(line_of(Capture::read(other.as_bytes())), 1);
+    }
+
+    #[test]
+    fn malformed_records_report_their_line() {
+        let header = "{\"format\":\"prelude-capture\",\"version\":1}\n";
+        let good = "{\"t_us\":1,\"kind\":\"pins\",\"state\":192}\n";
+        let bad = [
+            "{\"t_us\":2,\"kind\":\"tx\",\"dut\":1,\"data\":\"5g\"}",
+            "{\"t_us\":2,\"kind\":\"rx\",\"dut\":1,\"data\":\"abc\"}",
+            "{\"t_us\":2,\"kind\":\"rx\",\"dut\":1,\"data\":\"a\\u00e90\"}",
+            "{\"t_us\":2,\"kind\":\"rx\",\"dut\":1,\"data\":\"aé0\"}",
+            "{\"t_us\":2,\"kind\":\"tx\",\"dut\":0,\"data\":\"00\"}",
+            "{\"t_us\":2,\"kind\":\"tx\",\"dut\":3,\"data\":\"00\"}",
+            "{\"t_us\":2,\"kind\":\"reset\"}",
+            "{\"kind\":\"pins\",\"state\":192}",
+            "{\"t_us\":2,\"kind\":\"pins\",\"state\":256}",
+        ];
+        for line in bad {
+            let text = format!("{header}{good}\n{line}\n{good}");
+            assert_eq!(line_of(Capture::read(text.as_bytes())), 4, "{line}");
+        }
+    }
+}
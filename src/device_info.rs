@@ -0,0 +1,292 @@
+use crate::dut::DutClient;
+use crate::error::{PowerControllerError, Result};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Dotted firmware version such as `03.01.02.04`.
+///
+/// Versions compare component by component, a missing trailing component
+/// counting as zero, so `03.04.05` > `03.01.02.04` and `3.1` == `03.01.00`.
+#[derive(Debug, Clone)]
+pub struct FirmwareVersion {
+    parts: Vec<u32>,
+}
+
+impl FirmwareVersion {
+    pub fn parts(&self) -> &[u32] {
+        &self.parts
+    }
+
+    fn part(&self, i: usize) -> u32 {
+        self.parts.get(i).copied().unwrap_or(0)
+    }
+}
+
+impl FromStr for FirmwareVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let parts = s
+            .trim()
+            .split('.')
+            .map(|p| p.parse::<u32>())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|_| format!("invalid firmware version '{}'", s.trim()))?;
+        Ok(Self { parts })
+    }
+}
+
+impl Ord for FirmwareVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let len = self.parts.len().max(other.parts.len());
+        (0..len)
+            .map(|i| self.part(i).cmp(&other.part(i)))
+            .find(|o| o.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for FirmwareVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for FirmwareVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for FirmwareVersion {}
+
+/// Prints two digits per component, as the DUT reports it.
+impl fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self.parts.iter().map(|p| format!("{:02}", p)).collect();
+        write!(f, "{}", parts.join("."))
+    }
+}
+
+/// 48-bit Bluetooth address, reported by the DUT as 12 hex digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MacAddress(pub [u8; 6]);
+
+impl FromStr for MacAddress {
+    type Err = String;
+
+    /// Accepts `D01411205B83` as well as `D0:14:11:20:5B:83` / `D0-14-...`.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let digits: String = s
+            .trim()
+            .chars()
+            .filter(|c| *c != ':' && *c != '-')
+            .collect();
+        if digits.len() != 12 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("invalid MAC address '{}'", s.trim()));
+        }
+
+        let mut bytes = [0u8; 6];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16)
+                .map_err(|_| format!("invalid MAC address '{}'", s.trim()))?;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+impl fmt::Display for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let b = self.0;
+        write!(
+            f,
+            "{:02X}:{:02X}:{:02X}:{:02X}:{:02X}:{:02X}",
+            b[0], b[1], b[2], b[3], b[4], b[5]
+        )
+    }
+}
+
+/// Fields of the `[init_status,]` reply, e.g.
+///
+/// ```text
+/// Aw:Init
+/// Fw0Version:03.01.02.04
+/// Model Name: Bali
+/// PROD SN:25267359
+/// BT:D01411205B83
+/// Calib:230
+/// Mode0:NotDut
+/// ```
+///
+/// Keys the parser does not know are kept verbatim in `extra`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceInfo {
+    /// `Aw:` status, `Init` after boot
+    pub aw: Option<String>,
+    /// `Cw:` status, `Init` after boot
+    pub cw: Option<String>,
+    /// `Bat:T` / `Bat:F`
+    pub battery: Option<bool>,
+    pub fw0_version: Option<FirmwareVersion>,
+    pub fw1_version: Option<FirmwareVersion>,
+    pub model_id: Option<u32>,
+    pub model_name: Option<String>,
+    pub serial_number: Option<String>,
+    pub bt_address: Option<MacAddress>,
+    pub ble_address: Option<MacAddress>,
+    pub calibration: Option<u32>,
+    /// `Mode0:` value, `NotDut` on production firmware
+    pub mode0: Option<String>,
+    pub mode_fog: Option<u32>,
+    pub tpf: Option<u32>,
+    pub extra: BTreeMap<String, String>,
+}
+
+impl DeviceInfo {
+    /// Parses the text of an `[init_status,]` reply.
+    /// Blank lines are ignored; line numbers in errors are 1-based.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut info = DeviceInfo::default();
+
+        for (index, raw_line) in text.lines().enumerate() {
+            let line = raw_line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let fail = |reason: String| PowerControllerError::InitStatusParse {
+                line: index + 1,
+                text: line.to_string(),
+                reason,
+            };
+
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| fail("expected 'Key:Value'".to_string()))?;
+            let key = key.trim();
+            let value = value.trim();
+
+            match key {
+                "Aw" => info.aw = Some(value.to_string()),
+                "Cw" => info.cw = Some(value.to_string()),
+                "Bat" => {
+                    info.battery = Some(match value {
+                        "T" => true,
+                        "F" => false,
+                        _ => return Err(fail(format!("expected T or F, got '{}'", value))),
+                    })
+                }
+                "Fw0Version" => info.fw0_version = Some(value.parse().map_err(fail)?),
+                "Fw1Version" => info.fw1_version = Some(value.parse().map_err(fail)?),
+                "Model ID" => info.model_id = Some(parse_number(value).map_err(fail)?),
+                "Model Name" => info.model_name = Some(value.to_string()),
+                "PROD SN" => info.serial_number = Some(value.to_string()),
+                "BT" => info.bt_address = Some(value.parse().map_err(fail)?),
+                "BLE" => info.ble_address = Some(value.parse().map_err(fail)?),
+                "Calib" => info.calibration = Some(parse_number(value).map_err(fail)?),
+                "Mode0" => info.mode0 = Some(value.to_string()),
+                "Mode fog" => info.mode_fog = Some(parse_number(value).map_err(fail)?),
+                "TPF" => info.tpf = Some(parse_number(value).map_err(fail)?),
+                _ => {
+                    info.extra.insert(key.to_string(), value.to_string());
+                }
+            }
+        }
+
+        Ok(info)
+    }
+}
+
+impl FromStr for DeviceInfo {
+    type Err = PowerControllerError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+fn parse_number(value: &str) -> std::result::Result<u32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("expected a number, got '{}'", value))
+}
+
+impl DutClient {
+    /// Sends `[init_status,]` and parses the reply.
+    pub fn init_status(&mut self) -> Result<DeviceInfo> {
+        let response = self.command("init_status", &[])?;
+        DeviceInfo::parse(&response.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `[init_status,]` reply of a healthy Bali DUT, as captured on COM3.
+    const BALI: &str = "Aw:Init\r\nCw:Init\r\nBat:T\r\nFw0Version:03.01.02.04\r\n\
+        Fw1Version:03.04.05\r\nModel ID: 0\r\nModel Name: Bali\r\nPROD SN:25267359\r\n\
+        BT:D01411205B83\r\nBLE:D01411205B83\r\nCalib:230\r\nMode0:NotDut\r\n\
+        Mode fog: 0\r\nTPF: 0";
+
+    #[test]
+    fn parses_bali_init_status() {
+        let info = DeviceInfo::parse(BALI).unwrap();
+
+        assert_eq!(info.aw.as_deref(), Some("Init"));
+        assert_eq!(info.cw.as_deref(), Some("Init"));
+        assert_eq!(info.battery, Some(true));
+        assert_eq!(info.fw0_version.as_ref().unwrap().parts(), [3, 1, 2, 4]);
+        assert_eq!(info.fw1_version.as_ref().unwrap().to_string(), "03.04.05");
+        assert_eq!(info.model_id, Some(0));
+        assert_eq!(info.model_name.as_deref(), Some("Bali"));
+        assert_eq!(info.serial_number.as_deref(), Some("25267359"));
+        assert_eq!(
+            info.bt_address,
+            Some(MacAddress([0xD0, 0x14, 0x11, 0x20, 0x5B, 0x83]))
+        );
+        assert_eq!(info.ble_address, info.bt_address);
+        assert_eq!(info.calibration, Some(230));
+        assert_eq!(info.mode0.as_deref(), Some("NotDut"));
+        assert_eq!(info.mode_fog, Some(0));
+        assert_eq!(info.tpf, Some(0));
+        assert!(info.extra.is_empty());
+    }
+
+    #[test]
+    fn keeps_unknown_keys() {
+        let info: DeviceInfo = "Aw:Init\nHW Rev: B2\n".parse().unwrap();
+        assert_eq!(info.extra.get("HW Rev").map(String::as_str), Some("B2"));
+    }
+
+    #[test]
+    fn bad_line_reports_its_number() {
+        let text = "Aw:Init\r\n\r\nBat:T\r\nCalib:high\r\n";
+        match DeviceInfo::parse(text) {
+            Err(PowerControllerError::InitStatusParse { line, text, .. }) => {
+                assert_eq!(line, 4);
+                assert_eq!(text, "Calib:high");
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn line_without_separator_is_rejected() {
+        let result = DeviceInfo::parse("Aw:Init\ngarbage\n");
+        assert!(matches!(
+            result,
+            Err(PowerControllerError::InitStatusParse { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn compares_firmware_versions_numerically() {
+        let old: FirmwareVersion = "03.01.02.04".parse().unwrap();
+        let new: FirmwareVersion = "3.1.10".parse().unwrap();
+        assert!(new > old);
+        assert_eq!(old, "3.1.2.4".parse().unwrap());
+    }
+}
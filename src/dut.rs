@@ -0,0 +1,267 @@
+use crate::error::{PowerControllerError, Result};
+use crate::power::WireMode;
+use crate::transport::{PinTransport, SerialTransport};
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind};
+use std::time::{Duration, Instant};
+
+/// Default DUT UART speed (single-wire link).
+pub const DUT_BAUD_RATE: u32 = 9600;
+
+/// Builds a bracket protocol frame: `[name,arg1,arg2,]`.
+pub fn frame_command(name: &str, args: &[&str]) -> Vec<u8> {
+    let mut frame = String::with_capacity(name.len() + 3);
+    frame.push('[');
+    frame.push_str(name);
+    frame.push(',');
+    for arg in args {
+        frame.push_str(arg);
+        frame.push(',');
+    }
+    frame.push(']');
+    frame.into_bytes()
+}
+
+/// How `DutClient` decides that a response is complete.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DutOptions {
+    /// Give up if the DUT sends nothing at all within this time.
+    pub response_timeout: Duration,
+    /// Once data has arrived, the response ends after this much silence.
+    pub idle_gap: Duration,
+    /// Optional byte sequence that ends the response immediately.
+    pub terminator: Option<Vec<u8>>,
+    /// Read timeout used for each poll of the UART.
+    pub poll_interval: Duration,
+    /// The link reads back every byte written (single-wire); drop that
+    /// echo from the input.
+    pub echo: bool,
+}
+
+impl Default for DutOptions {
+    fn default() -> Self {
+        Self {
+            response_timeout: Duration::from_secs(3),
+            idle_gap: Duration::from_millis(300),
+            terminator: None,
+            poll_interval: Duration::from_millis(20),
+            echo: false,
+        }
+    }
+}
+
+impl DutOptions {
+    /// Defaults with the echo handling of `mode`.
+    pub fn for_mode(mode: WireMode) -> Self {
+        Self {
+            echo: mode.echoes(),
+            ..Self::default()
+        }
+    }
+}
+
+/// Reply collected from the DUT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    /// Frame that was sent, empty for unsolicited output.
+    pub command: Vec<u8>,
+    pub raw: Vec<u8>,
+    /// `raw` decoded as UTF-8, invalid sequences replaced.
+    pub text: String,
+    /// Time from the end of the write to the first received byte.
+    pub first_byte: Duration,
+    /// Time from the end of the write to the last received byte.
+    pub last_byte: Duration,
+}
+
+impl Response {
+    /// Non-empty lines of the reply, without CR/LF.
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.text
+            .lines()
+            .map(|line| line.trim_end_matches('\r'))
+            .filter(|line| !line.is_empty())
+    }
+}
+
+/// Client for the DUT UART bracket command protocol
+/// (`[init_status,]`, `[2700_shutdown,]`, ...).
+pub struct DutClient {
+    port: Box<dyn PinTransport>,
+    options: DutOptions,
+    /// Written bytes not read back yet.
+    echo: VecDeque<u8>,
+    /// Received bytes handed back by `unread`, served before the port.
+    pending: VecDeque<u8>,
+}
+
+impl DutClient {
+    /// Opens the DUT COM port (e.g. COM3/COM4) as 8N1 at the given baud rate.
+    pub fn open(port_name: &str, baud_rate: u32) -> Result<Self> {
+        let port = SerialTransport::open(port_name, baud_rate)?;
+        Self::with_transport(Box::new(port), DutOptions::default())
+    }
+
+    /// Opens the DUT COM port at the speed of `mode`, dropping the echo of
+    /// a single-wire link.
+    pub fn open_link(port_name: &str, mode: WireMode) -> Result<Self> {
+        let port = SerialTransport::open(port_name, mode.baud_rate())?;
+        Self::with_transport(Box::new(port), DutOptions::for_mode(mode))
+    }
+
+    /// Builds a client on top of an already opened transport.
+    pub fn with_transport(mut port: Box<dyn PinTransport>, options: DutOptions) -> Result<Self> {
+        port.set_timeout(options.poll_interval)?;
+        Ok(Self {
+            port,
+            options,
+            echo: VecDeque::new(),
+            pending: VecDeque::new(),
+        })
+    }
+
+    pub fn options(&self) -> &DutOptions {
+        &self.options
+    }
+
+    pub fn set_options(&mut self, options: DutOptions) -> Result<()> {
+        self.port.set_timeout(options.poll_interval)?;
+        if !options.echo {
+            self.echo.clear();
+        }
+        self.options = options;
+        Ok(())
+    }
+
+    /// Discards everything the DUT has sent so far. A DUT that keeps
+    /// streaming is drained for at most `response_timeout`.
+    pub fn clear_input(&mut self) -> Result<()> {
+        let deadline = Instant::now() + self.options.response_timeout;
+        let mut discard = [0u8; 1024];
+        loop {
+            match self.read_input(&mut discard) {
+                Ok(0) => return Ok(()),
+                Ok(_) if Instant::now() >= deadline => return Ok(()),
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::TimedOut => return Ok(()),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Writes raw bytes to the DUT.
+    pub fn write_raw(&mut self, data: &[u8]) -> Result<()> {
+        self.port.write_all(data)?;
+        let _ = self.port.flush();
+        if self.options.echo {
+            self.echo.extend(data);
+        }
+        Ok(())
+    }
+
+    /// Sends `[name,args...,]` without waiting for a reply.
+    pub fn send(&mut self, name: &str, args: &[&str]) -> Result<()> {
+        self.write_raw(&frame_command(name, args))
+    }
+
+    /// Clears pending input, sends `[name,args...,]` and collects the reply.
+    /// Fails with `Timeout` if the DUT stays silent.
+    pub fn command(&mut self, name: &str, args: &[&str]) -> Result<Response> {
+        let frame = frame_command(name, args);
+        self.clear_input()?;
+        self.write_raw(&frame)?;
+
+        let mut response = self.receive()?;
+        response.command = frame;
+        Ok(response)
+    }
+
+    /// Collects output until the idle gap or terminator, without sending
+    /// anything first. Fails with `Timeout` if nothing arrives.
+    pub fn receive(&mut self) -> Result<Response> {
+        let start = Instant::now();
+        let mut raw = Vec::new();
+        let mut first_byte = None;
+        let mut last_byte = start;
+        let mut buffer = [0u8; 512];
+
+        loop {
+            match self.read_input(&mut buffer) {
+                Ok(n) if n > 0 => {
+                    let now = Instant::now();
+                    first_byte.get_or_insert(now - start);
+                    last_byte = now;
+                    raw.extend_from_slice(&buffer[..n]);
+
+                    if let Some(terminator) = &self.options.terminator {
+                        if !terminator.is_empty()
+                            && raw.windows(terminator.len()).any(|w| w == &terminator[..])
+                        {
+                            break;
+                        }
+                    }
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::TimedOut => {}
+                Err(e) => return Err(e.into()),
+            }
+
+            match first_byte {
+                Some(_) if last_byte.elapsed() >= self.options.idle_gap => break,
+                None if start.elapsed() >= self.options.response_timeout => {
+                    return Err(PowerControllerError::Timeout)
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Response {
+            command: Vec::new(),
+            text: String::from_utf8_lossy(&raw).into_owned(),
+            raw,
+            first_byte: first_byte.unwrap_or_default(),
+            last_byte: last_byte - start,
+        })
+    }
+
+    /// Reads from the port, leaving out the echo of our own writes. Only
+    /// returns 0 when the port did.
+    pub(crate) fn read_input(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.pending.is_empty() {
+            let n = buf.len().min(self.pending.len());
+            for (slot, byte) in buf.iter_mut().zip(self.pending.drain(..n)) {
+                *slot = byte;
+            }
+            return Ok(n);
+        }
+
+        loop {
+            let n = self.port.read(buf)?;
+            let mut echoed = 0;
+            while echoed < n && self.echo.front() == Some(&buf[echoed]) {
+                self.echo.pop_front();
+                echoed += 1;
+            }
+            // Anything else means the echo was lost or is already behind us
+            if echoed < n {
+                self.echo.clear();
+            }
+            if n == 0 || echoed < n {
+                buf.copy_within(echoed..n, 0);
+                return Ok(n - echoed);
+            }
+        }
+    }
+
+    /// Puts received bytes back in front of the input.
+    pub(crate) fn unread(&mut self, data: &[u8]) {
+        for &byte in data.iter().rev() {
+            self.pending.push_front(byte);
+        }
+    }
+
+    /// Expose mutable reference to the underlying transport
+    pub fn port_mut(&mut self) -> &mut dyn PinTransport {
+        self.port.as_mut()
+    }
+}
@@ -1,3 +1,6 @@
+use crate::fixture::FtdiInterface;
+use crate::pins::PinState;
+use crate::shutdown::ShutdownStage;
 use std::io;
 use thiserror::Error;
 
@@ -9,14 +12,78 @@ pub enum PowerControllerError {
     #[error("Failed to configure serial port '{0}': {1}")]
     ConfigError(String, #[source] serialport::Error),
 
+    #[error("Failed to enumerate serial ports: {0}")]
+    PortEnumerationError(#[source] serialport::Error),
+
+    #[error("Fixture {serial} has no usable port for interface {interface}")]
+    FixturePortMissing {
+        serial: String,
+        interface: FtdiInterface,
+    },
+
+    #[error("No fixture with serial number '{0}'")]
+    FixtureNotFound(String),
+
+    #[error("Fixture '{0}' is already open in this process")]
+    FixtureInUse(String),
+
+    #[error("No reconnect function installed on the controller")]
+    ReconnectUnavailable,
+
+    #[error("Fixture watcher thread has stopped")]
+    WatcherStopped,
+
+    #[error("Log stream has stopped")]
+    LogStreamStopped,
+
+    #[error("FTDI D2XX error: {0}")]
+    FtdiError(#[from] libftd2xx::FtStatus),
+
     #[error("I/O error during communication: {0}")]
     IoError(#[from] io::Error),
 
     #[error("Timeout while waiting for device response")]
     Timeout,
 
+    #[error("Capture line {line}: {reason}")]
+    CaptureFormat { line: usize, reason: String },
+
+    #[error("Invalid expect pattern: {0}")]
+    InvalidPattern(#[from] regex::Error),
+
     #[error("Invalid device side specified")]
     InvalidDeviceSide,
+
+    #[error("Sequence step requires a DUT UART link")]
+    DutLinkRequired,
+
+    #[error("init_status line {line} '{text}': {reason}")]
+    InitStatusParse {
+        line: usize,
+        text: String,
+        reason: String,
+    },
+
+    #[error("DUT still responds after shutdown")]
+    DutStillResponding,
+
+    #[error("Shutdown failed at {stage}: {reason}")]
+    ShutdownFailed {
+        stage: ShutdownStage,
+        reason: String,
+    },
+
+    #[error("Transport '{0}' cannot read back pin levels")]
+    PinReadUnsupported(String),
+
+    #[error("Transport '{0}' cannot switch pin directions")]
+    PinDirectionUnsupported(String),
+
+    #[error("Pin state mismatch: wrote {expected:?}, read back {actual:?}")]
+    PinMismatch {
+        expected: PinState,
+        actual: PinState,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, PowerControllerError>;
@@ -0,0 +1,217 @@
+use crate::dut::DutClient;
+use crate::error::{PowerControllerError, Result};
+use crate::log_stream::LogLine;
+use regex::bytes::Regex;
+use std::io::ErrorKind;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// Output that `DutClient::expect` waits for.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// Exact bytes, e.g. `Aw:Init`.
+    Literal(Vec<u8>),
+    /// Matched against everything received so far, so a greedy tail such
+    /// as `.*` only covers what has arrived when it matches.
+    Regex(Regex),
+}
+
+impl Pattern {
+    pub fn literal(text: impl AsRef<[u8]>) -> Self {
+        Pattern::Literal(text.as_ref().to_vec())
+    }
+
+    /// Compiles a regex, e.g. `r"Fw0Version:(\S+)"`.
+    pub fn regex(pattern: &str) -> Result<Self> {
+        Ok(Pattern::Regex(Regex::new(pattern)?))
+    }
+
+    /// Start, end and capture groups of the first match.
+    fn find(&self, haystack: &[u8]) -> Option<(usize, usize, Vec<Option<String>>)> {
+        match self {
+            Pattern::Literal(literal) if literal.is_empty() => Some((0, 0, Vec::new())),
+            Pattern::Literal(literal) => haystack
+                .windows(literal.len())
+                .position(|w| w == &literal[..])
+                .map(|start| (start, start + literal.len(), Vec::new())),
+            Pattern::Regex(regex) => regex.captures(haystack).map(|captures| {
+                let whole = captures.get(0).unwrap();
+                let groups = captures
+                    .iter()
+                    .skip(1)
+                    .map(|group| group.map(|g| lossy(g.as_bytes())))
+                    .collect();
+                (whole.start(), whole.end(), groups)
+            }),
+        }
+    }
+}
+
+impl From<&str> for Pattern {
+    fn from(text: &str) -> Self {
+        Pattern::literal(text)
+    }
+}
+
+impl From<String> for Pattern {
+    fn from(text: String) -> Self {
+        Pattern::Literal(text.into_bytes())
+    }
+}
+
+impl From<Regex> for Pattern {
+    fn from(regex: Regex) -> Self {
+        Pattern::Regex(regex)
+    }
+}
+
+/// Output that satisfied an expect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectMatch {
+    /// Index of the matching pattern, always 0 for `expect`.
+    pub pattern: usize,
+    /// The matched text.
+    pub text: String,
+    /// Regex capture groups, group 1 first; `None` for a group that did
+    /// not take part in the match. Empty for literals.
+    pub groups: Vec<Option<String>>,
+    /// Output received before the match.
+    pub before: String,
+    /// Time from the call to the match.
+    pub elapsed: Duration,
+}
+
+impl DutClient {
+    /// Reads until `pattern` shows up, e.g. `dut.expect("Aw:Init", timeout)`
+    /// after power-on. See `expect_any`.
+    ///
+    /// A regex sees everything received since the call as one buffer and
+    /// is not multi-line: `^` and `$` anchor to the start and end of that
+    /// buffer, not of a line as in `expect_line`.
+    pub fn expect(
+        &mut self,
+        pattern: impl Into<Pattern>,
+        timeout: Duration,
+    ) -> Result<ExpectMatch> {
+        self.expect_any(&[pattern.into()], timeout)
+    }
+
+    /// Reads until one of `patterns` shows up. The match that starts first
+    /// in the output wins, ties go to the earlier pattern.
+    ///
+    /// Output after the match stays buffered for the next read. Fails with
+    /// `Timeout` if nothing matches in time; the output read meanwhile
+    /// stays buffered as well.
+    pub fn expect_any(&mut self, patterns: &[Pattern], timeout: Duration) -> Result<ExpectMatch> {
+        let start = Instant::now();
+        let mut seen = Vec::new();
+        let mut buffer = [0u8; 512];
+
+        loop {
+            match self.read_input(&mut buffer) {
+                Ok(n) => seen.extend_from_slice(&buffer[..n]),
+                Err(e) if e.kind() == ErrorKind::TimedOut => {}
+                Err(e) => {
+                    self.unread(&seen);
+                    return Err(e.into());
+                }
+            }
+
+            if let Some((pattern, from, to, groups)) = first_match(patterns, &seen) {
+                self.unread(&seen[to..]);
+                return Ok(ExpectMatch {
+                    pattern,
+                    text: lossy(&seen[from..to]),
+                    groups,
+                    before: lossy(&seen[..from]),
+                    elapsed: start.elapsed(),
+                });
+            }
+
+            if start.elapsed() >= timeout {
+                self.unread(&seen);
+                return Err(PowerControllerError::Timeout);
+            }
+        }
+    }
+}
+
+/// Line of a `LogStream` subscription that satisfied an expect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineMatch {
+    /// Index of the matching pattern, always 0 for `expect_line`.
+    pub pattern: usize,
+    /// The whole line, including the DUT it came from.
+    pub line: LogLine,
+    /// The matched part of the line.
+    pub text: String,
+    /// Regex capture groups, as in `ExpectMatch::groups`.
+    pub groups: Vec<Option<String>>,
+    /// Time from the call to the match.
+    pub elapsed: Duration,
+}
+
+/// Waits for a line matching `pattern` on a subscription, e.g.
+/// `expect_line(&stream.subscribe(), "Aw:Init", timeout)`, so a pattern
+/// matcher can run next to other consumers of the same UART. See
+/// `expect_any_line`.
+pub fn expect_line(
+    lines: &Receiver<LogLine>,
+    pattern: impl Into<Pattern>,
+    timeout: Duration,
+) -> Result<LineMatch> {
+    expect_any_line(lines, &[pattern.into()], timeout)
+}
+
+/// Waits for a line matching one of `patterns`. Each line is matched on
+/// its own, without the line ending; the match that starts first wins,
+/// ties go to the earlier pattern.
+///
+/// Lines before the match are consumed. Fails with `Timeout` if nothing
+/// matches in time and with `LogStreamStopped` once the stream is dropped.
+pub fn expect_any_line(
+    lines: &Receiver<LogLine>,
+    patterns: &[Pattern],
+    timeout: Duration,
+) -> Result<LineMatch> {
+    let start = Instant::now();
+    loop {
+        let remaining = timeout.saturating_sub(start.elapsed());
+        let line = lines.recv_timeout(remaining).map_err(|e| match e {
+            RecvTimeoutError::Timeout => PowerControllerError::Timeout,
+            RecvTimeoutError::Disconnected => PowerControllerError::LogStreamStopped,
+        })?;
+
+        let text = line.text.as_bytes();
+        if let Some((pattern, from, to, groups)) = first_match(patterns, text) {
+            return Ok(LineMatch {
+                pattern,
+                text: lossy(&text[from..to]),
+                groups,
+                line,
+                elapsed: start.elapsed(),
+            });
+        }
+    }
+}
+
+/// Index, start, end and capture groups of the match that starts first,
+/// ties going to the earlier pattern.
+fn first_match(
+    patterns: &[Pattern],
+    haystack: &[u8],
+) -> Option<(usize, usize, usize, Vec<Option<String>>)> {
+    patterns
+        .iter()
+        .enumerate()
+        .filter_map(|(index, pattern)| {
+            pattern
+                .find(haystack)
+                .map(|(from, to, groups)| (index, from, to, groups))
+        })
+        .min_by_key(|(index, from, _, _)| (*from, *index))
+}
+
+fn lossy(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
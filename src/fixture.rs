@@ -0,0 +1,440 @@
+use crate::dut::{DutClient, DutOptions};
+use crate::error::{PowerControllerError, Result};
+use crate::power::{
+    ConnectOptions, DeviceSide, PowerController, Reconnect, WireMode, CONTROL_BAUD_RATE,
+};
+use crate::transport::{D2xxTransport, PinTransport, SerialTransport};
+use serialport::{SerialPortInfo, SerialPortType};
+use std::fmt;
+
+/// FTDI vendor ID.
+const FTDI_VID: u16 = 0x0403;
+/// FT4232H product ID.
+const FT4232H_PID: u16 = 0x6011;
+/// Common part of the descriptions programmed into the fixture EEPROM;
+/// the interface letter follows, e.g. `FT4232H_Orka Prelude A`.
+const DESCRIPTION_PREFIX: &str = "FT4232H_Orka Prelude";
+
+/// One of the four channels of the FT4232H.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FtdiInterface {
+    A,
+    B,
+    C,
+    D,
+}
+
+impl FtdiInterface {
+    pub const ALL: [FtdiInterface; 4] = [Self::A, Self::B, Self::C, Self::D];
+
+    /// USB interface number, 0 for A up to 3 for D.
+    pub fn index(&self) -> u8 {
+        *self as u8
+    }
+
+    pub fn letter(&self) -> char {
+        (b'A' + self.index()) as char
+    }
+
+    pub fn from_index(index: u8) -> Option<Self> {
+        Self::ALL.get(index as usize).copied()
+    }
+
+    pub fn from_letter(letter: char) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|i| i.letter() == letter.to_ascii_uppercase())
+    }
+}
+
+impl fmt::Display for FtdiInterface {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.letter())
+    }
+}
+
+/// Where one FT4232H interface of a fixture can be reached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixturePort {
+    pub interface: FtdiInterface,
+    /// D2XX description, e.g. `FT4232H_Orka Prelude A`.
+    pub description: Option<String>,
+    /// D2XX serial number of the interface (base serial plus letter).
+    pub serial_number: Option<String>,
+    /// VCP device (`/dev/ttyUSB0`, `COM5`, ...), if the OS exposes one.
+    pub path: Option<String>,
+}
+
+impl FixturePort {
+    fn new(interface: FtdiInterface) -> Self {
+        Self {
+            interface,
+            description: None,
+            serial_number: None,
+            path: None,
+        }
+    }
+}
+
+/// One Prelude fixture and the port of each of its roles.
+///
+/// Interface mapping: A drives the power rails in bit-bang mode, C is the
+/// DUT1 UART, D the DUT2 UART; B is not used by the firmware.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixture {
+    /// USB serial number of the FT4232H, without the interface letter.
+    pub serial: String,
+    pub power_control: FixturePort,
+    pub spare: FixturePort,
+    pub dut1_uart: FixturePort,
+    pub dut2_uart: FixturePort,
+}
+
+impl Fixture {
+    fn new(serial: &str) -> Self {
+        Self {
+            serial: serial.to_string(),
+            power_control: FixturePort::new(FtdiInterface::A),
+            spare: FixturePort::new(FtdiInterface::B),
+            dut1_uart: FixturePort::new(FtdiInterface::C),
+            dut2_uart: FixturePort::new(FtdiInterface::D),
+        }
+    }
+
+    /// Finds every connected fixture through the D2XX driver and the OS
+    /// serial port list, sorted by serial number.
+    pub fn discover() -> Result<Vec<Fixture>> {
+        Self::rediscover(&[])
+    }
+
+    /// Same as `discover`, keeping the D2XX mapping of `previous` fixtures
+    /// for interfaces that are open and therefore listed without a
+    /// description or serial number.
+    pub fn rediscover(previous: &[Fixture]) -> Result<Vec<Fixture>> {
+        let devices = libftd2xx::list_devices()?;
+        let ports =
+            serialport::available_ports().map_err(PowerControllerError::PortEnumerationError)?;
+        Ok(Self::from_listings_with(&devices, &ports, previous))
+    }
+
+    /// Builds the fixture list from already collected D2XX and serial port
+    /// listings. Either listing may be empty.
+    pub fn from_listings(
+        devices: &[libftd2xx::DeviceInfo],
+        ports: &[SerialPortInfo],
+    ) -> Vec<Fixture> {
+        Self::from_listings_with(devices, ports, &[])
+    }
+
+    /// Same as `from_listings`, with the fixtures of an earlier listing.
+    ///
+    /// D2XX lists an interface that is already open (e.g. the power
+    /// control of a claimed board) with a blank description and serial.
+    /// Each such entry is attributed to an interface of `previous` that is
+    /// missing from the new listing, so the board keeps its last known
+    /// mapping instead of losing interface A or disappearing.
+    pub fn from_listings_with(
+        devices: &[libftd2xx::DeviceInfo],
+        ports: &[SerialPortInfo],
+        previous: &[Fixture],
+    ) -> Vec<Fixture> {
+        let mut fixtures: Vec<Fixture> = Vec::new();
+        let mut open_unknown = 0;
+
+        for device in devices {
+            if device.port_open && device.description.is_empty() {
+                open_unknown += 1;
+                continue;
+            }
+            let Some(interface) = description_interface(&device.description) else {
+                continue;
+            };
+            // D2XX appends the interface letter to the serial of multi-port chips
+            let base = device
+                .serial_number
+                .strip_suffix(interface.letter())
+                .unwrap_or(&device.serial_number);
+
+            let port = fixture_entry(&mut fixtures, base).port_mut(interface);
+            port.description = Some(device.description.clone());
+            port.serial_number = Some(device.serial_number.clone());
+        }
+
+        for info in ports {
+            let SerialPortType::UsbPort(usb) = &info.port_type else {
+                continue;
+            };
+            if usb.vid != FTDI_VID || usb.pid != FT4232H_PID {
+                continue;
+            }
+            let Some(serial) = usb.serial_number.as_deref() else {
+                continue;
+            };
+
+            let known = fixtures.iter().find_map(|fixture| {
+                if serial == fixture.serial {
+                    Some((fixture.serial.clone(), None))
+                } else {
+                    serial
+                        .strip_prefix(fixture.serial.as_str())
+                        .and_then(|rest| rest.chars().next().filter(|_| rest.len() == 1))
+                        .and_then(FtdiInterface::from_letter)
+                        .map(|interface| (fixture.serial.clone(), Some(interface)))
+                }
+            });
+
+            let (base, letter) = match known {
+                Some(found) => found,
+                None if usb
+                    .product
+                    .as_deref()
+                    .is_some_and(|p| p.contains("Orka Prelude")) =>
+                {
+                    (serial.to_string(), None)
+                }
+                None => continue,
+            };
+
+            let Some(interface) = letter.or_else(|| port_interface(info, &base)) else {
+                continue;
+            };
+            fixture_entry(&mut fixtures, &base).port_mut(interface).path =
+                Some(info.port_name.clone());
+        }
+
+        for old in previous {
+            if open_unknown == 0 {
+                break;
+            }
+            let missing: Vec<FtdiInterface> = FtdiInterface::ALL
+                .into_iter()
+                .filter(|&interface| old.port(interface).serial_number.is_some())
+                .filter(|&interface| {
+                    fixtures.iter().all(|f| {
+                        f.serial != old.serial || f.port(interface).serial_number.is_none()
+                    })
+                })
+                .collect();
+            if missing.is_empty() || missing.len() > open_unknown {
+                continue;
+            }
+            open_unknown -= missing.len();
+
+            let fixture = fixture_entry(&mut fixtures, &old.serial);
+            for interface in missing {
+                let port = fixture.port_mut(interface);
+                port.description = old.port(interface).description.clone();
+                port.serial_number = old.port(interface).serial_number.clone();
+            }
+        }
+
+        fixtures.sort_by(|a, b| a.serial.cmp(&b.serial));
+        fixtures
+    }
+
+    pub fn port(&self, interface: FtdiInterface) -> &FixturePort {
+        match interface {
+            FtdiInterface::A => &self.power_control,
+            FtdiInterface::B => &self.spare,
+            FtdiInterface::C => &self.dut1_uart,
+            FtdiInterface::D => &self.dut2_uart,
+        }
+    }
+
+    fn port_mut(&mut self, interface: FtdiInterface) -> &mut FixturePort {
+        match interface {
+            FtdiInterface::A => &mut self.power_control,
+            FtdiInterface::B => &mut self.spare,
+            FtdiInterface::C => &mut self.dut1_uart,
+            FtdiInterface::D => &mut self.dut2_uart,
+        }
+    }
+
+    /// UART port of one DUT; `Both` is rejected.
+    pub fn dut_uart(&self, side: DeviceSide) -> Result<&FixturePort> {
+        match side {
+            DeviceSide::Device1 => Ok(&self.dut1_uart),
+            DeviceSide::Device2 => Ok(&self.dut2_uart),
+            DeviceSide::Both => Err(PowerControllerError::InvalidDeviceSide),
+        }
+    }
+
+    /// Opens the power-control interface, through D2XX bit-bang when the
+    /// driver lists it and through the VCP otherwise.
+    pub fn connect_power(&self, options: ConnectOptions) -> Result<PowerController> {
+        PowerController::with_transport_options(self.open_power_transport()?, options)
+    }
+
+    /// Opens the power-control interface without driving any pins.
+    pub fn open_power_transport(&self) -> Result<Box<dyn PinTransport>> {
+        let port = &self.power_control;
+        match (&port.serial_number, &port.path) {
+            (Some(serial_number), _) => Ok(Box::new(D2xxTransport::open_serial(serial_number)?)),
+            (None, Some(path)) => Ok(Box::new(SerialTransport::open(path, CONTROL_BAUD_RATE)?)),
+            (None, None) => Err(self.missing(port.interface)),
+        }
+    }
+
+    /// `Reconnect` for `PowerController::set_reconnect` that reopens this
+    /// board's power-control interface.
+    pub fn power_reconnect(&self) -> Reconnect {
+        let fixture = self.clone();
+        Box::new(move || fixture.open_power_transport())
+    }
+
+    /// Opens the UART of one DUT for a single-wire link.
+    pub fn open_dut(&self, side: DeviceSide) -> Result<DutClient> {
+        self.open_dut_link(side, WireMode::SingleWire)
+    }
+
+    /// Opens the UART of one DUT with the speed and echo handling of `mode`.
+    pub fn open_dut_link(&self, side: DeviceSide, mode: WireMode) -> Result<DutClient> {
+        let port = self.open_dut_transport(side, mode.baud_rate())?;
+        DutClient::with_transport(port, DutOptions::for_mode(mode))
+    }
+
+    /// Opens the raw UART of one DUT as 8N1 at `baud_rate`.
+    pub fn open_dut_transport(
+        &self,
+        side: DeviceSide,
+        baud_rate: u32,
+    ) -> Result<Box<dyn PinTransport>> {
+        let port = self.dut_uart(side)?;
+        match &port.path {
+            Some(path) => Ok(Box::new(SerialTransport::open(path, baud_rate)?)),
+            None => Err(self.missing(port.interface)),
+        }
+    }
+
+    fn missing(&self, interface: FtdiInterface) -> PowerControllerError {
+        PowerControllerError::FixturePortMissing {
+            serial: self.serial.clone(),
+            interface,
+        }
+    }
+}
+
+fn fixture_entry<'a>(fixtures: &'a mut Vec<Fixture>, serial: &str) -> &'a mut Fixture {
+    match fixtures.iter().position(|f| f.serial == serial) {
+        Some(i) => &mut fixtures[i],
+        None => {
+            fixtures.push(Fixture::new(serial));
+            fixtures.last_mut().unwrap()
+        }
+    }
+}
+
+/// Interface letter at the end of a fixture description.
+fn description_interface(description: &str) -> Option<FtdiInterface> {
+    let rest = description.strip_prefix(DESCRIPTION_PREFIX)?.trim();
+    let mut chars = rest.chars();
+    match (chars.next(), chars.next()) {
+        (Some(letter), None) => FtdiInterface::from_letter(letter),
+        _ => None,
+    }
+}
+
+/// Interface number reported by the OS, or the digit macOS appends to the
+/// serial in the device name (`/dev/cu.usbserial-FT66ORKA2`).
+fn port_interface(info: &SerialPortInfo, serial: &str) -> Option<FtdiInterface> {
+    if let SerialPortType::UsbPort(usb) = &info.port_type {
+        if let Some(index) = usb.interface {
+            return FtdiInterface::from_index(index);
+        }
+    }
+    let (_, suffix) = info.port_name.rsplit_once(serial)?;
+    suffix
+        .parse::<u8>()
+        .ok()
+        .and_then(FtdiInterface::from_index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libftd2xx::DeviceInfo;
+
+    fn listed(serial: &str, interface: FtdiInterface) -> DeviceInfo {
+        DeviceInfo {
+            vendor_id: FTDI_VID,
+            product_id: FT4232H_PID,
+            serial_number: format!("{serial}{interface}"),
+            description: format!("{DESCRIPTION_PREFIX} {interface}"),
+            ..DeviceInfo::default()
+        }
+    }
+
+    fn opened() -> DeviceInfo {
+        DeviceInfo {
+            port_open: true,
+            ..DeviceInfo::default()
+        }
+    }
+
+    #[test]
+    fn maps_interfaces_by_description() {
+        let devices: Vec<_> = FtdiInterface::ALL
+            .into_iter()
+            .map(|i| listed("FT66ORKA", i))
+            .collect();
+        let fixtures = Fixture::from_listings(&devices, &[]);
+
+        assert_eq!(fixtures.len(), 1);
+        assert_eq!(fixtures[0].serial, "FT66ORKA");
+        assert_eq!(
+            fixtures[0].power_control.serial_number.as_deref(),
+            Some("FT66ORKAA")
+        );
+        assert_eq!(
+            fixtures[0].dut2_uart.description.as_deref(),
+            Some("FT4232H_Orka Prelude D")
+        );
+    }
+
+    #[test]
+    fn open_interface_keeps_the_previous_mapping() {
+        let mut devices: Vec<_> = FtdiInterface::ALL
+            .into_iter()
+            .map(|i| listed("FT66ORKA", i))
+            .collect();
+        let previous = Fixture::from_listings(&devices, &[]);
+
+        devices[0] = opened();
+        assert!(Fixture::from_listings(&devices, &[])[0]
+            .power_control
+            .serial_number
+            .is_none());
+        assert_eq!(
+            Fixture::from_listings_with(&devices, &[], &previous),
+            previous
+        );
+    }
+
+    #[test]
+    fn fully_open_board_is_kept() {
+        let devices = [
+            listed("FT66ORKA", FtdiInterface::A),
+            listed("FT77ORKA", FtdiInterface::A),
+        ];
+        let previous = Fixture::from_listings(&devices, &[]);
+
+        let devices = [opened(), listed("FT77ORKA", FtdiInterface::A)];
+        assert_eq!(
+            Fixture::from_listings_with(&devices, &[], &previous),
+            previous
+        );
+    }
+
+    #[test]
+    fn open_entries_are_not_attributed_to_detached_boards() {
+        let devices = [
+            listed("FT66ORKA", FtdiInterface::A),
+            listed("FT66ORKA", FtdiInterface::C),
+        ];
+        let previous = Fixture::from_listings(&devices, &[]);
+
+        // One open entry cannot stand for both interfaces of the board
+        let fixtures = Fixture::from_listings_with(&[opened()], &[], &previous);
+        assert!(fixtures.is_empty());
+    }
+}
@@ -1,8 +1,53 @@
+pub mod baud;
+pub mod boot_log;
+pub mod capture;
+pub mod device_info;
+pub mod dut;
 pub mod error;
+pub mod expect;
+pub mod fixture;
+pub mod log_stream;
+pub mod pins;
 pub mod power;
+pub mod registry;
+pub mod sequence;
+pub mod shutdown;
+#[cfg(feature = "sim")]
+pub mod sim;
+pub mod single_wire;
+pub mod soft_uart;
+pub mod transport;
+pub mod vcd;
+pub mod watcher;
 
 // Exporting typical Integration file for Tauri as module (not compiled strictly by default unless invoked)
 pub mod tauri_integration;
 
+pub use baud::{detect_baud, detect_baud_with, BaudCandidate, BaudDetection};
+pub use boot_log::{BootLine, BootLog, BootLogCapture, LogChunk, LogGap};
+pub use capture::{Capture, CaptureEvent, CaptureRecord, Recorder};
+#[cfg(feature = "sim")]
+pub use capture::{Replay, ReplayPort};
+pub use device_info::{DeviceInfo, FirmwareVersion, MacAddress};
+pub use dut::{DutClient, DutOptions, Response};
 pub use error::{PowerControllerError, Result};
-pub use power::{DeviceSide, PowerController, WireMode};
+pub use expect::{expect_any_line, expect_line, ExpectMatch, LineMatch, Pattern};
+pub use fixture::{Fixture, FixturePort, FtdiInterface};
+pub use log_stream::{LogLine, LogStream, LogStreamOptions};
+pub use pins::PinState;
+pub use power::{
+    ConnectOptions, DeviceSide, InitialState, PowerController, Reconnect, ResetOptions, WireMode,
+    CONTROL_BAUD_RATE,
+};
+pub use registry::{FixtureHandle, FixtureRegistry};
+pub use sequence::{PowerSequence, Rail, SequenceStep};
+pub use shutdown::{ShutdownPolicy, ShutdownReport, ShutdownStage, StageOutcome, SHUTDOWN_COMMAND};
+#[cfg(feature = "sim")]
+pub use sim::{
+    DutPower, DutScript, PinEvent, SimDutPort, SimTransport, SimulatedDut, SimulatedFixture,
+};
+pub use single_wire::{SingleWire, SingleWireOptions};
+pub use soft_uart::{decode_samples, IdlePolarity, SoftUartConfig, SoftUartDecoder};
+pub use transport::{D2xxTransport, PinTransport, SerialTransport, PRELUDE_POWER_DESCRIPTION};
+pub use vcd::{bitbang_sample_rate, export_vcd, VcdOptions, VcdWriter};
+pub use watcher::{FixtureEvent, FixtureWatcher};
@@ -0,0 +1,243 @@
+use crate::boot_log::sides;
+use crate::error::Result;
+use crate::fixture::Fixture;
+use crate::power::{DeviceSide, WireMode};
+use crate::transport::PinTransport;
+use std::collections::VecDeque;
+use std::io::ErrorKind;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime};
+
+/// One line received from a DUT UART.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogLine {
+    pub dut: DeviceSide,
+    /// Arrival of the first byte of the line.
+    pub timestamp: SystemTime,
+    /// `raw` decoded as UTF-8 without CR/LF, invalid sequences replaced.
+    pub text: String,
+    /// Bytes as received, including the line ending.
+    pub raw: Vec<u8>,
+}
+
+/// Tuning of a `LogStream`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogStreamOptions {
+    /// Lines kept for `history` and `subscribe_with_history`.
+    pub history: usize,
+    /// Read timeout of each poll of a UART.
+    pub poll_interval: Duration,
+    /// A partial line (e.g. a prompt) is emitted after this much silence.
+    pub flush_after: Duration,
+}
+
+impl Default for LogStreamOptions {
+    fn default() -> Self {
+        Self {
+            history: 1000,
+            poll_interval: Duration::from_millis(20),
+            flush_after: Duration::from_millis(500),
+        }
+    }
+}
+
+struct Hub {
+    history: VecDeque<LogLine>,
+    capacity: usize,
+    subscribers: Vec<Sender<LogLine>>,
+    errors: Vec<(DeviceSide, String)>,
+}
+
+impl Hub {
+    fn publish(&mut self, line: LogLine) {
+        // Receivers that were dropped are forgotten on the next line
+        self.subscribers.retain(|tx| tx.send(line.clone()).is_ok());
+        if self.capacity > 0 {
+            if self.history.len() == self.capacity {
+                self.history.pop_front();
+            }
+            self.history.push_back(line);
+        }
+    }
+}
+
+/// Reads DUT UARTs in background threads and fans their lines out to any
+/// number of subscribers.
+///
+/// Every subscriber gets its own copy of each line, so a UI, a file writer
+/// and a pattern matcher can consume the same UART side by side. The most
+/// recent lines are kept in a bounded ring buffer. The threads stop when
+/// the stream is dropped.
+pub struct LogStream {
+    hub: Arc<Mutex<Hub>>,
+    stop: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl LogStream {
+    /// Streams the UART of `side` (or both DUTs) of the fixture, opened at
+    /// the baud rate of `mode`.
+    pub fn open_fixture(
+        fixture: &Fixture,
+        side: DeviceSide,
+        mode: WireMode,
+        options: LogStreamOptions,
+    ) -> Result<Self> {
+        let mut ports = Vec::new();
+        for side in sides(side) {
+            ports.push((side, fixture.open_dut_transport(side, mode.baud_rate())?));
+        }
+        Ok(Self::spawn(ports, options))
+    }
+
+    /// Starts one reader per port; each port must belong to a single DUT.
+    pub fn spawn(
+        ports: Vec<(DeviceSide, Box<dyn PinTransport>)>,
+        options: LogStreamOptions,
+    ) -> Self {
+        let hub = Arc::new(Mutex::new(Hub {
+            history: VecDeque::with_capacity(options.history),
+            capacity: options.history,
+            subscribers: Vec::new(),
+            errors: Vec::new(),
+        }));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let threads = ports
+            .into_iter()
+            .map(|(dut, port)| {
+                let hub = hub.clone();
+                let stop = stop.clone();
+                std::thread::spawn(move || read_lines(dut, port, options, hub, stop))
+            })
+            .collect();
+
+        Self { hub, stop, threads }
+    }
+
+    /// Lines received from now on.
+    pub fn subscribe(&self) -> Receiver<LogLine> {
+        let (tx, rx) = mpsc::channel();
+        self.lock().subscribers.push(tx);
+        rx
+    }
+
+    /// The buffered history followed by every new line, without losing or
+    /// repeating a line in between.
+    pub fn subscribe_with_history(&self) -> Receiver<LogLine> {
+        let (tx, rx) = mpsc::channel();
+        let mut hub = self.lock();
+        for line in &hub.history {
+            let _ = tx.send(line.clone());
+        }
+        hub.subscribers.push(tx);
+        rx
+    }
+
+    /// Recent lines, oldest first.
+    pub fn history(&self) -> Vec<LogLine> {
+        self.lock().history.iter().cloned().collect()
+    }
+
+    /// UARTs whose reader stopped on an I/O error, with the error.
+    pub fn errors(&self) -> Vec<(DeviceSide, String)> {
+        self.lock().errors.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Hub> {
+        lock(&self.hub)
+    }
+}
+
+impl Drop for LogStream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn lock(hub: &Mutex<Hub>) -> MutexGuard<'_, Hub> {
+    hub.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Line being assembled from one UART.
+struct Partial {
+    raw: Vec<u8>,
+    timestamp: SystemTime,
+    last_byte: Instant,
+}
+
+impl Partial {
+    fn finish(self, dut: DeviceSide) -> Option<LogLine> {
+        let text = String::from_utf8_lossy(&self.raw);
+        let text = text.trim_end_matches(['\r', '\n']).to_string();
+        (!text.is_empty()).then_some(LogLine {
+            dut,
+            timestamp: self.timestamp,
+            text,
+            raw: self.raw,
+        })
+    }
+}
+
+fn read_lines(
+    dut: DeviceSide,
+    mut port: Box<dyn PinTransport>,
+    options: LogStreamOptions,
+    hub: Arc<Mutex<Hub>>,
+    stop: Arc<AtomicBool>,
+) {
+    let publish = |partial: Partial| {
+        if let Some(line) = partial.finish(dut) {
+            lock(&hub).publish(line);
+        }
+    };
+
+    if let Err(e) = port.set_timeout(options.poll_interval) {
+        lock(&hub).errors.push((dut, e.to_string()));
+        return;
+    }
+
+    let mut partial: Option<Partial> = None;
+    let mut buffer = [0u8; 1024];
+    while !stop.load(Ordering::Relaxed) {
+        let n = match port.read(&mut buffer) {
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::TimedOut => 0,
+            Err(e) => {
+                lock(&hub).errors.push((dut, e.to_string()));
+                break;
+            }
+        };
+
+        let now = Instant::now();
+        for &byte in &buffer[..n] {
+            let line = partial.get_or_insert_with(|| Partial {
+                raw: Vec::new(),
+                timestamp: SystemTime::now(),
+                last_byte: now,
+            });
+            line.raw.push(byte);
+            line.last_byte = now;
+            if byte == b'\n' {
+                publish(partial.take().unwrap());
+            }
+        }
+
+        if partial
+            .as_ref()
+            .is_some_and(|p| p.last_byte.elapsed() >= options.flush_after)
+        {
+            publish(partial.take().unwrap());
+        }
+    }
+
+    if let Some(partial) = partial {
+        publish(partial);
+    }
+}
@@ -0,0 +1,174 @@
+use crate::power::DeviceSide;
+use bitflags::bitflags;
+use std::fmt;
+
+bitflags! {
+    /// Level of the 8 GPIO pins on the power-control interface (data[6] of
+    /// the state payload). Bit assignment derived from PreludeSettings.h.
+    #[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+    pub struct PinState: u8 {
+        const RESET1 = 0x01;
+        const RESET2 = 0x02;
+        const VCHARGER1 = 0x04;
+        const VCHARGER2 = 0x08;
+        const POW1 = 0x10;
+        const POW2 = 0x20;
+
+        // DB6/DB7 are not wired to the DUTs but are still driven
+        const _ = !0;
+    }
+}
+
+impl PinState {
+    /// Every pin wired to a DUT rail or reset line.
+    pub const MANAGED: Self = Self::RESET1
+        .union(Self::RESET2)
+        .union(Self::VCHARGER1)
+        .union(Self::VCHARGER2)
+        .union(Self::POW1)
+        .union(Self::POW2);
+
+    /// POW (5V) pin(s) of the given side.
+    pub fn pow(side: DeviceSide) -> Self {
+        Self::per_side(side, Self::POW1, Self::POW2)
+    }
+
+    /// VCHARGER pin(s) of the given side.
+    pub fn vcharger(side: DeviceSide) -> Self {
+        Self::per_side(side, Self::VCHARGER1, Self::VCHARGER2)
+    }
+
+    /// RESET pin(s) of the given side.
+    pub fn reset(side: DeviceSide) -> Self {
+        Self::per_side(side, Self::RESET1, Self::RESET2)
+    }
+
+    /// Both supplies (POW and VCHARGER) of the given side.
+    pub fn supply(side: DeviceSide) -> Self {
+        Self::pow(side) | Self::vcharger(side)
+    }
+
+    /// All managed pins of the given side.
+    pub fn side(side: DeviceSide) -> Self {
+        Self::supply(side) | Self::reset(side)
+    }
+
+    fn per_side(side: DeviceSide, dut1: Self, dut2: Self) -> Self {
+        match side {
+            DeviceSide::Device1 => dut1,
+            DeviceSide::Device2 => dut2,
+            DeviceSide::Both => dut1 | dut2,
+        }
+    }
+
+    pub fn pow1(&self) -> bool {
+        self.contains(Self::POW1)
+    }
+
+    pub fn pow2(&self) -> bool {
+        self.contains(Self::POW2)
+    }
+
+    pub fn vcharger1(&self) -> bool {
+        self.contains(Self::VCHARGER1)
+    }
+
+    pub fn vcharger2(&self) -> bool {
+        self.contains(Self::VCHARGER2)
+    }
+
+    pub fn reset1(&self) -> bool {
+        self.contains(Self::RESET1)
+    }
+
+    pub fn reset2(&self) -> bool {
+        self.contains(Self::RESET2)
+    }
+
+    /// True if either supply (POW or VCHARGER) of the side is on.
+    pub fn is_supplied(&self, side: DeviceSide) -> bool {
+        self.intersects(Self::supply(side))
+    }
+
+    fn fmt_side(&self, f: &mut fmt::Formatter<'_>, side: DeviceSide) -> fmt::Result {
+        let names = [
+            (Self::pow(side), "POW"),
+            (Self::vcharger(side), "VCHG"),
+            (Self::reset(side), "RST"),
+        ];
+        let active: Vec<&str> = names
+            .iter()
+            .filter(|(pin, _)| self.contains(*pin))
+            .map(|(_, name)| *name)
+            .collect();
+
+        if active.is_empty() {
+            write!(f, "[-]")
+        } else {
+            write!(f, "[{}]", active.join(","))
+        }
+    }
+}
+
+impl From<u8> for PinState {
+    fn from(bits: u8) -> Self {
+        Self::from_bits_retain(bits)
+    }
+}
+
+impl From<PinState> for u8 {
+    fn from(state: PinState) -> Self {
+        state.bits()
+    }
+}
+
+/// Prints the managed pins per DUT, e.g. `DUT1[POW,VCHG] DUT2[-]`.
+impl fmt::Display for PinState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DUT1")?;
+        self.fmt_side(f, DeviceSide::Device1)?;
+        write!(f, " DUT2")?;
+        self.fmt_side(f, DeviceSide::Device2)
+    }
+}
+
+/// Same as `Display`, followed by the raw byte, e.g. `DUT1[POW] DUT2[-] (0xD0)`.
+impl fmt::Debug for PinState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (0x{:02X})", self, self.bits())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_lists_the_active_rails_per_dut() {
+        let state = PinState::POW1 | PinState::VCHARGER1;
+        assert_eq!(state.to_string(), "DUT1[POW,VCHG] DUT2[-]");
+        assert_eq!(
+            PinState::side(DeviceSide::Device2).to_string(),
+            "DUT1[-] DUT2[POW,VCHG,RST]"
+        );
+        // DB6/DB7 only show up in the raw byte
+        let state = PinState::from(0xC0) | PinState::POW1;
+        assert_eq!(format!("{state:?}"), "DUT1[POW] DUT2[-] (0xD0)");
+    }
+
+    #[test]
+    fn bytes_round_trip_including_db6_db7() {
+        for bits in [0x00, 0x15, 0x3F, 0x40, 0x80, 0xC0, 0xFF] {
+            let state = PinState::from(bits);
+            assert_eq!(u8::from(state), bits);
+            assert_eq!(state.bits(), bits);
+        }
+    }
+
+    #[test]
+    fn managed_pins_leave_db6_db7_out() {
+        assert_eq!(PinState::MANAGED.complement().bits(), 0xC0);
+        assert_eq!(PinState::MANAGED.bits(), 0x3F);
+        assert_eq!(PinState::side(DeviceSide::Both), PinState::MANAGED);
+    }
+}
@@ -1,15 +1,9 @@
+use crate::dut::DutClient;
 use crate::error::{PowerControllerError, Result};
-use serialport::SerialPort;
+use crate::pins::PinState;
+use crate::transport::{D2xxTransport, PinTransport, SerialTransport};
 use std::time::Duration;
 
-// Hardware Constants derived from PreludeSettings.h
-const VCHARGER1: u8 = 0x04;
-const VCHARGER2: u8 = 0x08;
-const POW1: u8 = 0x10;
-const POW2: u8 = 0x20;
-const RESET1: u8 = 0x01;
-const RESET2: u8 = 0x02;
-
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DeviceSide {
     Device1,
@@ -17,156 +11,351 @@ pub enum DeviceSide {
     Both,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Speed of the power-control interface when it is opened as a VCP. The
+/// payload and pins do not depend on the DUT link.
+pub const CONTROL_BAUD_RATE: u32 = 9600;
+
+/// How the DUT UART (interface C/D) reaches the DUT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum WireMode {
-    SingleWire, // Default, 9600 baud
-    DoubleWire, // 192000 baud
+    /// TX and RX share the DUT charge contact: half duplex at 9600 baud,
+    /// and every byte the host sends is read back.
+    #[default]
+    SingleWire,
+    /// Separate TX and RX lines: full duplex at 192000 baud, no echo.
+    DoubleWire,
 }
 
 impl WireMode {
+    /// Speed of the DUT UART.
     pub fn baud_rate(&self) -> u32 {
         match self {
             WireMode::SingleWire => 9600,
             WireMode::DoubleWire => 192000,
         }
     }
+
+    /// The host reads its own writes back on this link.
+    pub fn echoes(&self) -> bool {
+        matches!(self, WireMode::SingleWire)
+    }
 }
 
+/// Pin state applied when a controller attaches to a fixture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InitialState {
+    /// Drive every rail off and release reset (power-cycles running DUTs).
+    #[default]
+    ForceOff,
+    /// Adopt the live pin levels read from the hardware without writing.
+    Preserve,
+    /// Drive the given state immediately.
+    Explicit(PinState),
+}
+
+/// Shape of the hardware RESET pulse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResetOptions {
+    /// How long RESET stays asserted.
+    pub width: Duration,
+    /// The fixture inverts the RESET line: asserted = pin low, idle = pin high.
+    pub active_low: bool,
+    /// Time to wait after releasing RESET before returning.
+    pub settle: Duration,
+}
+
+impl Default for ResetOptions {
+    fn default() -> Self {
+        Self {
+            width: Duration::from_millis(100),
+            active_low: false,
+            settle: Duration::ZERO,
+        }
+    }
+}
+
+impl ResetOptions {
+    /// Drives the RESET pin(s) of the side to the asserted or idle level.
+    pub fn drive(&self, pins: &mut PinState, side: DeviceSide, asserted: bool) {
+        pins.set(PinState::reset(side), asserted != self.active_low);
+    }
+}
+
+/// Options for attaching a `PowerController` to a fixture.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConnectOptions {
+    pub initial_state: InitialState,
+    /// See `PowerController::set_verify_writes`.
+    pub verify_writes: bool,
+    /// Default pulse used by `PowerController::reset`; its polarity also
+    /// decides the idle RESET level written by `InitialState::ForceOff`.
+    pub reset: ResetOptions,
+    /// DUT link used by `PowerController::open_dut`.
+    pub wire_mode: WireMode,
+}
+
+/// Reopens the power-control transport after the board re-enumerated.
+pub type Reconnect = Box<dyn FnMut() -> Result<Box<dyn PinTransport>> + Send>;
+
 pub struct PowerController {
-    port: Box<dyn SerialPort>,
-    current_state: u8, // Tracks the byte status for data[6]
+    port: Box<dyn PinTransport>,
+    current_state: PinState, // Tracks the byte status for data[6]
+    verify_writes: bool,
+    reset_options: ResetOptions,
+    reconnect: Option<Reconnect>,
+    wire_mode: WireMode,
 }
 
 impl PowerController {
-    /// Initializes and opens the serial port with the specified mode.
-    /// By default, user requested SingleWire mode (9600).
+    /// Opens the power-control serial port; `mode` is the DUT link that
+    /// `open_dut` will configure. By default, user requested SingleWire mode (9600).
     pub fn connect(port_name: &str, mode: WireMode) -> Result<Self> {
-        let baud_rate = mode.baud_rate();
+        Self::connect_with(port_name, mode, ConnectOptions::default())
+    }
 
-        let port = serialport::new(port_name, baud_rate)
-            .timeout(Duration::from_millis(5000))
-            .data_bits(serialport::DataBits::Eight)
-            .parity(serialport::Parity::None) // Assuming default 8N1
-            .stop_bits(serialport::StopBits::One)
-            .flow_control(serialport::FlowControl::None)
-            .open()
-            .map_err(|e| PowerControllerError::PortOpenError(port_name.to_string(), e))?;
+    /// Same as `connect`, with control over the initial pin state. `mode`
+    /// replaces `options.wire_mode`.
+    pub fn connect_with(port_name: &str, mode: WireMode, options: ConnectOptions) -> Result<Self> {
+        let port = SerialTransport::open(port_name, CONTROL_BAUD_RATE)?;
+        let options = ConnectOptions {
+            wire_mode: mode,
+            ..options
+        };
+        Self::with_transport_options(Box::new(port), options)
+    }
+
+    /// Opens the FTDI interface with the given description through the native
+    /// D2XX driver in asynchronous bit-bang mode, e.g. "FT4232H_Orka Prelude A".
+    pub fn connect_d2xx(description: &str) -> Result<Self> {
+        Self::connect_d2xx_with(description, ConnectOptions::default())
+    }
+
+    /// Same as `connect_d2xx`, with control over the initial pin state.
+    pub fn connect_d2xx_with(description: &str, options: ConnectOptions) -> Result<Self> {
+        let port = D2xxTransport::open(description)?;
+        Self::with_transport_options(Box::new(port), options)
+    }
+
+    /// Builds a controller on top of an already opened transport.
+    /// This lets callers pick the backend (VCP serial, D2XX bit-bang, ...) at runtime.
+    pub fn with_transport(port: Box<dyn PinTransport>) -> Result<Self> {
+        Self::with_transport_options(port, ConnectOptions::default())
+    }
 
+    /// Same as `with_transport`, with control over the initial pin state.
+    pub fn with_transport_options(
+        port: Box<dyn PinTransport>,
+        options: ConnectOptions,
+    ) -> Result<Self> {
         // Initialize state to all power off and no reset
-        let initial_state =
-            0xFF & (!VCHARGER1) & (!VCHARGER2) & (!POW1) & (!POW2) & (!RESET1) & (!RESET2);
+        let mut off_state = PinState::MANAGED.complement();
+        options.reset.drive(&mut off_state, DeviceSide::Both, false);
 
         let mut controller = Self {
             port,
-            current_state: initial_state,
+            current_state: off_state,
+            verify_writes: options.verify_writes,
+            reset_options: options.reset,
+            reconnect: None,
+            wire_mode: options.wire_mode,
         };
 
-        // Ensure starting with all configured power off
-        controller.sync_state()?;
+        // Probe the read-back before the first write, so a transport that
+        // cannot observe the pins is rejected without touching them
+        if options.verify_writes {
+            controller.read_pins()?;
+        }
+
+        match options.initial_state {
+            // Ensure starting with all configured power off
+            InitialState::ForceOff => controller.sync_state()?,
+            // Reconnecting tools adopt the live state, as the C++ original
+            // did with data_read[6], so running DUTs are not power-cycled.
+            // Nothing is written, so an unreadable transport fails untouched
+            InitialState::Preserve => controller.current_state = controller.read_pins()?,
+            InitialState::Explicit(state) => {
+                controller.current_state = state;
+                controller.sync_state()?;
+            }
+        }
 
         Ok(controller)
     }
 
     /// Power ON the target device(s)
     pub fn power_on(&mut self, side: DeviceSide) -> Result<()> {
-        match side {
-            DeviceSide::Device1 => self.current_state |= POW1,
-            DeviceSide::Device2 => self.current_state |= POW2,
-            DeviceSide::Both => {
-                self.current_state |= POW1;
-                self.current_state |= POW2;
-            }
-        }
+        self.current_state.insert(PinState::pow(side));
         self.sync_state()
     }
 
     /// Power OFF the target device(s)
     pub fn power_off(&mut self, side: DeviceSide) -> Result<()> {
-        match side {
-            DeviceSide::Device1 => self.current_state &= !POW1,
-            DeviceSide::Device2 => self.current_state &= !POW2,
-            DeviceSide::Both => {
-                self.current_state &= !POW1;
-                self.current_state &= !POW2;
-            }
-        }
+        self.current_state.remove(PinState::pow(side));
         self.sync_state()
     }
 
     /// Enable VCHARGER for the target device(s)
     pub fn enable_vcharger(&mut self, side: DeviceSide) -> Result<()> {
-        match side {
-            DeviceSide::Device1 => self.current_state |= VCHARGER1,
-            DeviceSide::Device2 => self.current_state |= VCHARGER2,
-            DeviceSide::Both => {
-                self.current_state |= VCHARGER1;
-                self.current_state |= VCHARGER2;
-            }
-        }
+        self.current_state.insert(PinState::vcharger(side));
         self.sync_state()
     }
 
     /// Disable VCHARGER for the target device(s)
     pub fn disable_vcharger(&mut self, side: DeviceSide) -> Result<()> {
-        match side {
-            DeviceSide::Device1 => self.current_state &= !VCHARGER1,
-            DeviceSide::Device2 => self.current_state &= !VCHARGER2,
-            DeviceSide::Both => {
-                self.current_state &= !VCHARGER1;
-                self.current_state &= !VCHARGER2;
-            }
-        }
+        self.current_state.remove(PinState::vcharger(side));
         self.sync_state()
     }
 
-    /// Execute a hardware RESET pulse for 100ms
+    /// Drive the given pin state in a single payload write.
+    pub fn set_state(&mut self, state: PinState) -> Result<()> {
+        self.current_state = state;
+        self.sync_state()
+    }
+
+    /// Compute the next pin state from the current one and write it in a
+    /// single payload, so the rails never pass through intermediate
+    /// combinations (e.g. POW1 without VCHARGER1):
+    ///
+    /// `controller.apply(|pins| pins.insert(PinState::supply(DeviceSide::Device1)))?`
+    pub fn apply<F>(&mut self, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut PinState),
+    {
+        let mut next = self.current_state;
+        f(&mut next);
+        self.set_state(next)
+    }
+
+    /// Execute a hardware RESET pulse using the controller's default
+    /// `ResetOptions` (100ms, active-high unless configured otherwise)
     pub fn reset(&mut self, side: DeviceSide) -> Result<()> {
+        self.reset_with(side, self.reset_options)
+    }
+
+    /// Execute a hardware RESET pulse with an explicit width, polarity and
+    /// settle time
+    pub fn reset_with(&mut self, side: DeviceSide, options: ResetOptions) -> Result<()> {
         // Assert RESET
-        match side {
-            DeviceSide::Device1 => self.current_state |= RESET1,
-            DeviceSide::Device2 => self.current_state |= RESET2,
-            DeviceSide::Both => {
-                self.current_state |= RESET1;
-                self.current_state |= RESET2;
-            }
-        }
-        self.sync_state()?;
+        self.apply(|pins| options.drive(pins, side, true))?;
 
-        std::thread::sleep(Duration::from_millis(100));
+        std::thread::sleep(options.width);
 
         // De-assert RESET
-        match side {
-            DeviceSide::Device1 => self.current_state &= !RESET1,
-            DeviceSide::Device2 => self.current_state &= !RESET2,
-            DeviceSide::Both => {
-                self.current_state &= !RESET1;
-                self.current_state &= !RESET2;
+        self.apply(|pins| options.drive(pins, side, false))?;
+
+        std::thread::sleep(options.settle);
+        Ok(())
+    }
+
+    /// Default reset pulse used by `reset`.
+    pub fn reset_options(&self) -> ResetOptions {
+        self.reset_options
+    }
+
+    pub fn set_reset_options(&mut self, options: ResetOptions) {
+        self.reset_options = options;
+    }
+
+    /// DUT link this controller was connected for.
+    pub fn wire_mode(&self) -> WireMode {
+        self.wire_mode
+    }
+
+    /// Opens the DUT UART at `port_name` with the speed and echo handling of
+    /// `wire_mode`.
+    pub fn open_dut(&self, port_name: &str) -> Result<DutClient> {
+        DutClient::open_link(port_name, self.wire_mode)
+    }
+
+    /// Installs the function used to reopen the transport once the link is
+    /// lost, e.g. `fixture.power_reconnect()`. A write that fails with an
+    /// I/O or D2XX error then reconnects and retries once.
+    pub fn set_reconnect(&mut self, reconnect: Option<Reconnect>) {
+        self.reconnect = reconnect;
+    }
+
+    /// Reopens the transport through the installed `Reconnect` and restores
+    /// the last commanded pin state, e.g. on a `FixtureEvent::Attached`.
+    pub fn reconnect(&mut self) -> Result<()> {
+        let reconnect = self
+            .reconnect
+            .as_mut()
+            .ok_or(PowerControllerError::ReconnectUnavailable)?;
+        let port = reconnect()?;
+        self.replace_transport(port)
+    }
+
+    /// Swaps in a freshly opened transport and restores the last commanded
+    /// pin state on it.
+    pub fn replace_transport(&mut self, port: Box<dyn PinTransport>) -> Result<()> {
+        self.port = port;
+        if self.verify_writes {
+            self.read_pins()?;
+        }
+        self.write_state()
+    }
+
+    /// Sync internal state to the hardware, reconnecting once if the link
+    /// was lost and a `Reconnect` is installed.
+    fn sync_state(&mut self) -> Result<()> {
+        match self.write_state() {
+            Err(PowerControllerError::IoError(_) | PowerControllerError::FtdiError(_))
+                if self.reconnect.is_some() =>
+            {
+                self.reconnect()
             }
+            result => result,
         }
-        self.sync_state()
     }
 
-    /// Sync internal state to the hardware
     /// Creates a 7-byte command payload where index 6 contains the hardware masks.
-    /// This abstracts the bit-bang data packet for an external UART adapter.
-    fn sync_state(&mut self) -> Result<()> {
+    /// This abstracts the bit-bang data packet for an external UART adapter;
+    /// bit-bang transports send the state byte in place of the preamble.
+    fn write_state(&mut self) -> Result<()> {
         // Construct the 7-byte payload as per original protocol
-        // Original logic alternated 0x55/0xAA or read input; we zero pad until state byte
-        let payload: [u8; 7] = [0x55, 0x55, 0x55, 0x55, 0x55, 0x55, self.current_state];
+        let mut payload = [0x55u8; 7];
+        payload[6] = self.current_state.bits();
 
-        self.port
-            .write_all(&payload)
-            .map_err(PowerControllerError::IoError)?;
+        self.port.write_payload(&payload)?;
 
-        // Optional flush
-        let _ = self.port.flush();
+        if self.verify_writes {
+            let actual = PinState::from(self.port.read_pins()?);
+            if (actual ^ self.current_state).intersects(PinState::MANAGED) {
+                return Err(PowerControllerError::PinMismatch {
+                    expected: self.current_state,
+                    actual,
+                });
+            }
+        }
+
+        Ok(())
+    }
 
+    /// Last pin state commanded (or adopted) by this controller.
+    pub fn state(&self) -> PinState {
+        self.current_state
+    }
+
+    /// Reads the actual pin levels back from the hardware.
+    pub fn read_pins(&mut self) -> Result<PinState> {
+        self.port.read_pins().map(PinState::from)
+    }
+
+    /// When enabled, every state write is followed by a pin read-back and
+    /// fails with `PinMismatch` if the hardware disagrees. Enabling probes
+    /// the read-back once and fails with `PinReadUnsupported` (leaving
+    /// verification off) if the transport cannot observe the pins.
+    pub fn set_verify_writes(&mut self, verify: bool) -> Result<()> {
+        if verify {
+            self.read_pins()?;
+        }
+        self.verify_writes = verify;
         Ok(())
     }
 
-    /// Expose mutable reference to the underlying serial port for reading logs
-    pub fn port_mut(&mut self) -> &mut Box<dyn SerialPort> {
-        &mut self.port
+    /// Expose mutable reference to the underlying transport for reading logs
+    pub fn port_mut(&mut self) -> &mut dyn PinTransport {
+        self.port.as_mut()
     }
 }
 
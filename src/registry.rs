@@ -0,0 +1,137 @@
+use crate::dut::DutClient;
+use crate::error::{PowerControllerError, Result};
+use crate::fixture::Fixture;
+use crate::power::{ConnectOptions, DeviceSide, PowerController};
+use std::collections::BTreeSet;
+use std::sync::{Mutex, MutexGuard};
+
+/// Serial numbers of the fixtures currently held by a `FixtureHandle` in
+/// this process.
+static CLAIMED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+fn claimed() -> MutexGuard<'static, BTreeSet<String>> {
+    CLAIMED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Releases the fixture when dropped.
+struct Claim {
+    serial: String,
+}
+
+impl Claim {
+    fn acquire(serial: &str) -> Result<Self> {
+        if !claimed().insert(serial.to_string()) {
+            return Err(PowerControllerError::FixtureInUse(serial.to_string()));
+        }
+        Ok(Self {
+            serial: serial.to_string(),
+        })
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        claimed().remove(&self.serial);
+    }
+}
+
+/// All Prelude boards attached to the host, keyed by FTDI serial number.
+///
+/// Opening through the registry instead of `connect_d2xx("FT4232H_Orka
+/// Prelude A")` picks a specific board and refuses to hand the same board
+/// out twice within the process.
+#[derive(Debug, Clone, Default)]
+pub struct FixtureRegistry {
+    fixtures: Vec<Fixture>,
+}
+
+impl FixtureRegistry {
+    /// Lists the attached boards with `Fixture::discover`.
+    pub fn discover() -> Result<Self> {
+        Ok(Self::from_fixtures(Fixture::discover()?))
+    }
+
+    /// Lists the attached boards again. Boards claimed in this process have
+    /// their power-control interface open, so D2XX no longer names it; the
+    /// mapping listed before is kept for them.
+    pub fn refresh(&mut self) -> Result<()> {
+        self.fixtures = Fixture::rediscover(&self.fixtures)?;
+        Ok(())
+    }
+
+    pub fn from_fixtures(fixtures: Vec<Fixture>) -> Self {
+        Self { fixtures }
+    }
+
+    pub fn fixtures(&self) -> &[Fixture] {
+        &self.fixtures
+    }
+
+    pub fn serials(&self) -> impl Iterator<Item = &str> {
+        self.fixtures.iter().map(|f| f.serial.as_str())
+    }
+
+    pub fn get(&self, serial: &str) -> Option<&Fixture> {
+        self.fixtures.iter().find(|f| f.serial == serial)
+    }
+
+    /// True while a `FixtureHandle` for the board is alive in this process.
+    pub fn is_claimed(serial: &str) -> bool {
+        claimed().contains(serial)
+    }
+
+    /// Claims the board and opens its power-control interface.
+    /// Fails with `FixtureInUse` if another handle already holds it.
+    pub fn open(&self, serial: &str, options: ConnectOptions) -> Result<FixtureHandle> {
+        self.open_with(serial, |fixture| fixture.connect_power(options))
+    }
+
+    /// Claims the board around a controller built by the caller, e.g. on a
+    /// simulated transport.
+    pub fn open_with(
+        &self,
+        serial: &str,
+        connect: impl FnOnce(&Fixture) -> Result<PowerController>,
+    ) -> Result<FixtureHandle> {
+        let fixture = self
+            .get(serial)
+            .ok_or_else(|| PowerControllerError::FixtureNotFound(serial.to_string()))?;
+        let claim = Claim::acquire(serial)?;
+        let power = connect(fixture)?;
+        Ok(FixtureHandle {
+            fixture: fixture.clone(),
+            power,
+            _claim: claim,
+        })
+    }
+}
+
+/// Exclusive access to one board; the board is released on drop.
+pub struct FixtureHandle {
+    fixture: Fixture,
+    power: PowerController,
+    // Declared last so the board is released after the controller is closed
+    _claim: Claim,
+}
+
+impl FixtureHandle {
+    pub fn fixture(&self) -> &Fixture {
+        &self.fixture
+    }
+
+    pub fn serial(&self) -> &str {
+        &self.fixture.serial
+    }
+
+    pub fn power(&mut self) -> &mut PowerController {
+        &mut self.power
+    }
+
+    /// Opens the UART of one DUT on this board, for the link the controller
+    /// was connected with.
+    pub fn open_dut(&self, side: DeviceSide) -> Result<DutClient> {
+        self.fixture.open_dut_link(side, self.power.wire_mode())
+    }
+}
@@ -0,0 +1,229 @@
+use crate::dut::{frame_command, DutClient};
+use crate::error::{PowerControllerError, Result};
+use crate::pins::PinState;
+use crate::power::{DeviceSide, PowerController, ResetOptions};
+use crate::shutdown::{ShutdownPolicy, SHUTDOWN_COMMAND};
+use std::time::Duration;
+
+/// One rail of a DUT slot, resolved to concrete pins when a sequence runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rail {
+    Pow,
+    Vcharger,
+    Reset,
+}
+
+impl Rail {
+    pub fn pins(&self, side: DeviceSide) -> PinState {
+        match self {
+            Rail::Pow => PinState::pow(side),
+            Rail::Vcharger => PinState::vcharger(side),
+            Rail::Reset => PinState::reset(side),
+        }
+    }
+}
+
+/// Turns the rails on or off; RESET follows the configured polarity, so
+/// enabling it always means "asserted".
+fn set_rails(pins: &mut PinState, rails: &[Rail], side: DeviceSide, on: bool, reset: ResetOptions) {
+    for rail in rails {
+        match rail {
+            Rail::Reset => reset.drive(pins, side, on),
+            _ => pins.set(rail.pins(side), on),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SequenceStep {
+    /// Drive the rails high, in a single write.
+    Enable(Vec<Rail>),
+    /// Drive the rails low, in a single write.
+    Disable(Vec<Rail>),
+    /// Drive both supplies of the side low and release reset.
+    AllOff,
+    /// Pulse RESET with the controller's `ResetOptions` (width, polarity
+    /// and settle time).
+    Reset,
+    Delay(Duration),
+    /// Write raw bytes to the DUT UART.
+    Send(Vec<u8>),
+    /// Discard whatever the DUT UART has buffered so far, for at most the
+    /// client's response timeout.
+    Drain,
+    /// Read the DUT UART until the text shows up, failing with `Timeout`.
+    WaitFor {
+        text: String,
+        timeout: Duration,
+    },
+}
+
+/// Ordered power choreography for one DUT slot, run by
+/// `PowerController::run_sequence`. Steps are relative to the side the
+/// sequence is run on, so the same profile drives DUT1, DUT2 or both.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PowerSequence {
+    steps: Vec<SequenceStep>,
+}
+
+impl PowerSequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn step(mut self, step: SequenceStep) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    pub fn enable(self, rails: &[Rail]) -> Self {
+        self.step(SequenceStep::Enable(rails.to_vec()))
+    }
+
+    pub fn disable(self, rails: &[Rail]) -> Self {
+        self.step(SequenceStep::Disable(rails.to_vec()))
+    }
+
+    pub fn all_off(self) -> Self {
+        self.step(SequenceStep::AllOff)
+    }
+
+    pub fn reset(self) -> Self {
+        self.step(SequenceStep::Reset)
+    }
+
+    pub fn delay(self, delay: Duration) -> Self {
+        self.step(SequenceStep::Delay(delay))
+    }
+
+    pub fn send(self, data: &[u8]) -> Self {
+        self.step(SequenceStep::Send(data.to_vec()))
+    }
+
+    pub fn drain(self) -> Self {
+        self.step(SequenceStep::Drain)
+    }
+
+    pub fn wait_for(self, text: &str, timeout: Duration) -> Self {
+        self.step(SequenceStep::WaitFor {
+            text: text.to_string(),
+            timeout,
+        })
+    }
+
+    pub fn steps(&self) -> &[SequenceStep] {
+        &self.steps
+    }
+
+    /// True if any step talks to the DUT UART.
+    pub fn needs_uart(&self) -> bool {
+        self.steps.iter().any(|step| {
+            matches!(
+                step,
+                SequenceStep::Send(_) | SequenceStep::Drain | SequenceStep::WaitFor { .. }
+            )
+        })
+    }
+
+    /// Full cold start as in `hotplug_capture.rs`: hold every pin low for 2s
+    /// so the DUT really loses power, raise VCHARGER, then POW 100ms later,
+    /// and give the firmware 3s to boot.
+    pub fn cold_boot() -> Self {
+        Self::new()
+            .all_off()
+            .delay(Duration::from_secs(2))
+            .enable(&[Rail::Vcharger])
+            .delay(Duration::from_millis(100))
+            .enable(&[Rail::Pow])
+            .delay(Duration::from_secs(3))
+    }
+
+    /// Restart through the RESET line with both supplies kept on, using the
+    /// controller's reset pulse.
+    pub fn warm_boot() -> Self {
+        Self::new()
+            .enable(&[Rail::Vcharger, Rail::Pow])
+            .reset()
+            .delay(Duration::from_secs(3))
+    }
+
+    /// `PowerController::shutdown` with the default `ShutdownPolicy`, see
+    /// `shutdown`.
+    pub fn graceful_shutdown() -> Self {
+        Self::shutdown(&ShutdownPolicy::default())
+    }
+
+    /// The rail choreography of `PowerController::shutdown` under `policy`:
+    /// `[2700_shutdown,]` (unless disabled), 5V off, then VCHARGER off. The
+    /// DUT is re-activated by 5V if the rails are left on. The silence check
+    /// is not part of the sequence; needs a DUT UART if the command is sent.
+    pub fn shutdown(policy: &ShutdownPolicy) -> Self {
+        let seq = if policy.send_command {
+            Self::new()
+                .drain()
+                .send(&frame_command(SHUTDOWN_COMMAND, &[]))
+                .delay(policy.command_delay)
+        } else {
+            Self::new()
+        };
+        seq.disable(&[Rail::Pow])
+            .delay(policy.rail_gap)
+            .disable(&[Rail::Vcharger])
+    }
+}
+
+impl PowerController {
+    /// Runs a sequence that only touches the rails.
+    /// Fails with `DutLinkRequired` if the sequence contains UART steps.
+    pub fn run_sequence(&mut self, side: DeviceSide, seq: &PowerSequence) -> Result<()> {
+        self.run_sequence_with(side, seq, None)
+    }
+
+    /// Runs a sequence whose UART steps go through `dut`, so the echo of a
+    /// single-wire link is dropped before `WaitFor` looks at the output.
+    pub fn run_sequence_with(
+        &mut self,
+        side: DeviceSide,
+        seq: &PowerSequence,
+        mut dut: Option<&mut DutClient>,
+    ) -> Result<()> {
+        if seq.needs_uart() && dut.is_none() {
+            return Err(PowerControllerError::DutLinkRequired);
+        }
+
+        let reset = self.reset_options();
+        for step in seq.steps() {
+            match step {
+                SequenceStep::Enable(rails) => {
+                    self.apply(|pins| set_rails(pins, rails, side, true, reset))?
+                }
+                SequenceStep::Disable(rails) => {
+                    self.apply(|pins| set_rails(pins, rails, side, false, reset))?
+                }
+                SequenceStep::AllOff => self.apply(|pins| {
+                    pins.remove(PinState::supply(side));
+                    reset.drive(pins, side, false);
+                })?,
+                SequenceStep::Reset => self.reset_with(side, reset)?,
+                SequenceStep::Delay(delay) => std::thread::sleep(*delay),
+                SequenceStep::Send(data) => {
+                    if let Some(dut) = dut.as_deref_mut() {
+                        dut.write_raw(data)?;
+                    }
+                }
+                SequenceStep::Drain => {
+                    if let Some(dut) = dut.as_deref_mut() {
+                        dut.clear_input()?;
+                    }
+                }
+                SequenceStep::WaitFor { text, timeout } => {
+                    if let Some(dut) = dut.as_deref_mut() {
+                        dut.expect(text.as_str(), *timeout)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
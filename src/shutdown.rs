@@ -0,0 +1,203 @@
+use crate::dut::DutClient;
+use crate::error::{PowerControllerError, Result};
+use crate::pins::PinState;
+use crate::power::{DeviceSide, PowerController};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Name of the DUT command that starts a software shutdown, sent as
+/// `[2700_shutdown,]`.
+pub const SHUTDOWN_COMMAND: &str = "2700_shutdown";
+
+/// How `PowerController::shutdown` takes a DUT down.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShutdownPolicy {
+    /// Send `[2700_shutdown,]` before touching the rails.
+    pub send_command: bool,
+    /// Time the firmware gets to process the command before 5V is removed.
+    pub command_delay: Duration,
+    /// Pause between removing POW (5V) and VCHARGER.
+    pub rail_gap: Duration,
+    /// Probe with `[init_status,]` until the DUT stays silent.
+    pub verify: bool,
+    /// Give up verifying after this long.
+    pub verify_deadline: Duration,
+    /// How long a single probe waits for an answer.
+    pub probe_timeout: Duration,
+}
+
+impl Default for ShutdownPolicy {
+    fn default() -> Self {
+        Self {
+            send_command: true,
+            command_delay: Duration::from_secs(1),
+            rail_gap: Duration::from_millis(100),
+            verify: true,
+            verify_deadline: Duration::from_secs(5),
+            probe_timeout: Duration::from_millis(500),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownStage {
+    Command,
+    PowerOff,
+    VchargerOff,
+    Verify,
+}
+
+impl fmt::Display for ShutdownStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ShutdownStage::Command => "shutdown command",
+            ShutdownStage::PowerOff => "5V off",
+            ShutdownStage::VchargerOff => "VCHARGER off",
+            ShutdownStage::Verify => "silence check",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StageOutcome {
+    Done,
+    Skipped,
+    Failed(String),
+}
+
+/// Result of every shutdown stage, in execution order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShutdownReport {
+    pub stages: Vec<(ShutdownStage, StageOutcome)>,
+    /// Time from the start of the shutdown until the DUT was found silent.
+    pub silent_after: Option<Duration>,
+}
+
+impl ShutdownReport {
+    pub fn outcome(&self, stage: ShutdownStage) -> Option<&StageOutcome> {
+        self.stages
+            .iter()
+            .find(|(s, _)| *s == stage)
+            .map(|(_, outcome)| outcome)
+    }
+
+    /// First stage that failed, if any.
+    pub fn failed_stage(&self) -> Option<ShutdownStage> {
+        self.stages
+            .iter()
+            .find(|(_, outcome)| matches!(outcome, StageOutcome::Failed(_)))
+            .map(|(stage, _)| *stage)
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failed_stage().is_none()
+    }
+
+    /// Converts the first failed stage into `ShutdownFailed`.
+    pub fn into_result(self) -> Result<Self> {
+        for (stage, outcome) in &self.stages {
+            if let StageOutcome::Failed(reason) = outcome {
+                return Err(PowerControllerError::ShutdownFailed {
+                    stage: *stage,
+                    reason: reason.clone(),
+                });
+            }
+        }
+        Ok(self)
+    }
+}
+
+fn outcome<T>(result: Result<T>) -> StageOutcome {
+    match result {
+        Ok(_) => StageOutcome::Done,
+        Err(e) => StageOutcome::Failed(e.to_string()),
+    }
+}
+
+impl PowerController {
+    /// Graceful shutdown of one DUT: `[2700_shutdown,]`, then 5V off, then
+    /// VCHARGER off, then check that the DUT no longer answers. The command
+    /// alone is not enough, the DUT is re-activated while 5V is present.
+    ///
+    /// Every stage runs even if an earlier one failed so the rails always end
+    /// up off; the report tells which stage went wrong.
+    pub fn shutdown(
+        &mut self,
+        side: DeviceSide,
+        dut: &mut DutClient,
+        policy: &ShutdownPolicy,
+    ) -> Result<ShutdownReport> {
+        if side == DeviceSide::Both {
+            return Err(PowerControllerError::InvalidDeviceSide);
+        }
+
+        let start = Instant::now();
+        let mut report = ShutdownReport {
+            stages: Vec::new(),
+            silent_after: None,
+        };
+
+        if policy.send_command {
+            let sent = dut
+                .clear_input()
+                .and_then(|_| dut.send(SHUTDOWN_COMMAND, &[]));
+            report.stages.push((ShutdownStage::Command, outcome(sent)));
+            std::thread::sleep(policy.command_delay);
+        } else {
+            report
+                .stages
+                .push((ShutdownStage::Command, StageOutcome::Skipped));
+        }
+
+        let pow_off = self.apply(|pins| pins.remove(PinState::pow(side)));
+        report
+            .stages
+            .push((ShutdownStage::PowerOff, outcome(pow_off)));
+        std::thread::sleep(policy.rail_gap);
+
+        let vcharger_off = self.apply(|pins| pins.remove(PinState::vcharger(side)));
+        report
+            .stages
+            .push((ShutdownStage::VchargerOff, outcome(vcharger_off)));
+
+        if policy.verify {
+            let verified = verify_silent(dut, policy);
+            if verified.is_ok() {
+                report.silent_after = Some(start.elapsed());
+            }
+            report
+                .stages
+                .push((ShutdownStage::Verify, outcome(verified)));
+        } else {
+            report
+                .stages
+                .push((ShutdownStage::Verify, StageOutcome::Skipped));
+        }
+
+        Ok(report)
+    }
+}
+
+/// Probes with `[init_status,]` until a probe times out or the deadline passes.
+fn verify_silent(dut: &mut DutClient, policy: &ShutdownPolicy) -> Result<()> {
+    let saved = dut.options().clone();
+    let mut probe = saved.clone();
+    probe.response_timeout = policy.probe_timeout;
+    dut.set_options(probe)?;
+
+    let deadline = Instant::now() + policy.verify_deadline;
+    let result = loop {
+        match dut.command("init_status", &[]) {
+            Err(PowerControllerError::Timeout) => break Ok(()),
+            Err(e) => break Err(e),
+            Ok(_) if Instant::now() >= deadline => {
+                break Err(PowerControllerError::DutStillResponding);
+            }
+            Ok(_) => {}
+        }
+    };
+
+    dut.set_options(saved)?;
+    result
+}
@@ -0,0 +1,595 @@
+//! In-memory Prelude fixture for exercising `PowerController` without hardware.
+//!
+//! `SimulatedFixture` owns the simulated pin levels. Calling
+//! [`SimulatedFixture::transport`] hands out a `PinTransport` that decodes the
+//! 7-byte state payload written by the controller, sent the way the D2XX
+//! bit-bang transport does (the state byte repeated), and records
+//! every pin change with a timestamp, so tests can assert on the history.
+//!
+//! [`SimulatedDut`] is a scriptable Bali DUT powered by those pins. Its UART
+//! end, [`SimDutPort`], speaks the bracket command protocol and plugs in
+//! wherever the library expects a `PinTransport`.
+use crate::error::{PowerControllerError, Result};
+use crate::pins::PinState;
+use crate::power::{DeviceSide, WireMode};
+use crate::shutdown::SHUTDOWN_COMMAND;
+use crate::transport::{bitbang_payload, PinTransport};
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Length of one state payload written by `PowerController::sync_state`.
+const PAYLOAD_LEN: usize = 7;
+
+/// One decoded state write, timestamped relative to fixture creation.
+/// `state` is the resulting pin level, including any stuck pins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PinEvent {
+    pub at: Duration,
+    pub state: PinState,
+}
+
+struct FixtureState {
+    started: Instant,
+    pins: PinState,
+    history: Vec<PinEvent>,
+    pending: Vec<u8>,
+    malformed: usize,
+    stuck_mask: PinState,
+    stuck_level: PinState,
+    rx: VecDeque<u8>,
+}
+
+struct Shared {
+    state: Mutex<FixtureState>,
+    rx_ready: Condvar,
+}
+
+/// Handle to a simulated Prelude board. Cheap to clone; all clones and all
+/// transports created from it observe the same pins.
+#[derive(Clone)]
+pub struct SimulatedFixture {
+    shared: Arc<Shared>,
+}
+
+impl Default for SimulatedFixture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimulatedFixture {
+    /// Creates a fixture with every pin low.
+    pub fn new() -> Self {
+        Self::with_pins(PinState::empty())
+    }
+
+    /// Creates a fixture whose pins start at the given level.
+    pub fn with_pins(pins: PinState) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                state: Mutex::new(FixtureState {
+                    started: Instant::now(),
+                    pins,
+                    history: Vec::new(),
+                    pending: Vec::new(),
+                    malformed: 0,
+                    stuck_mask: PinState::empty(),
+                    stuck_level: PinState::empty(),
+                    rx: VecDeque::new(),
+                }),
+                rx_ready: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Creates a transport connected to this fixture, to be passed to
+    /// `PowerController::with_transport`.
+    pub fn transport(&self) -> SimTransport {
+        SimTransport {
+            fixture: self.clone(),
+            timeout: Duration::from_millis(5000),
+        }
+    }
+
+    /// Current pin level.
+    pub fn pins(&self) -> PinState {
+        self.lock().pins
+    }
+
+    /// Every decoded state write, oldest first.
+    pub fn history(&self) -> Vec<PinEvent> {
+        self.lock().history.clone()
+    }
+
+    /// Forgets the recorded history, keeping the current pin level.
+    pub fn clear_history(&self) {
+        self.lock().history.clear();
+    }
+
+    /// Number of payloads that drove the pins through other levels before
+    /// the state byte, i.e. were not the state byte repeated.
+    pub fn malformed_payloads(&self) -> usize {
+        self.lock().malformed
+    }
+
+    /// Time elapsed since the fixture was created, on the history clock.
+    pub fn elapsed(&self) -> Duration {
+        self.lock().started.elapsed()
+    }
+
+    /// Simulates a hardware fault: pins in `mask` stay at `level` no matter
+    /// what the controller writes. Pass an empty mask to clear the fault.
+    pub fn stick_pins(&self, mask: PinState, level: PinState) {
+        let mut st = self.lock();
+        st.stuck_mask = mask;
+        st.stuck_level = level;
+    }
+
+    /// Queues bytes that the controller will receive on its next reads.
+    pub fn inject_rx(&self, data: &[u8]) {
+        self.lock().rx.extend(data.iter().copied());
+        self.shared.rx_ready.notify_all();
+    }
+
+    fn events_from(&self, start: usize) -> Vec<PinEvent> {
+        let st = self.lock();
+        st.history
+            .get(start..)
+            .map(<[_]>::to_vec)
+            .unwrap_or_default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, FixtureState> {
+        self.shared
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn feed(&self, data: &[u8]) {
+        let mut st = self.lock();
+        st.pending.extend_from_slice(data);
+
+        while st.pending.len() >= PAYLOAD_LEN {
+            let frame: Vec<u8> = st.pending.drain(..PAYLOAD_LEN).collect();
+            if frame.iter().any(|&b| b != frame[PAYLOAD_LEN - 1]) {
+                st.malformed += 1;
+            }
+
+            let written = PinState::from(frame[PAYLOAD_LEN - 1]);
+            let state = (written - st.stuck_mask) | (st.stuck_level & st.stuck_mask);
+            let at = st.started.elapsed();
+            st.pins = state;
+            st.history.push(PinEvent { at, state });
+        }
+    }
+}
+
+/// `PinTransport` end of a [`SimulatedFixture`].
+pub struct SimTransport {
+    fixture: SimulatedFixture,
+    timeout: Duration,
+}
+
+impl SimTransport {
+    /// The fixture this transport is wired to.
+    pub fn fixture(&self) -> &SimulatedFixture {
+        &self.fixture
+    }
+}
+
+impl Read for SimTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let deadline = Instant::now() + self.timeout;
+        let mut st = self.fixture.lock();
+        while st.rx.is_empty() {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "Operation timed out",
+                ));
+            }
+            st = self
+                .fixture
+                .shared
+                .rx_ready
+                .wait_timeout(st, deadline - now)
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .0;
+        }
+
+        let n = buf.len().min(st.rx.len());
+        for (slot, byte) in buf.iter_mut().zip(st.rx.drain(..n)) {
+            *slot = byte;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for SimTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.fixture.feed(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl PinTransport for SimTransport {
+    fn write_payload(&mut self, payload: &[u8]) -> Result<()> {
+        self.write_all(&bitbang_payload(payload))?;
+        Ok(())
+    }
+
+    fn read_pins(&mut self) -> Result<u8> {
+        Ok(self.fixture.pins().bits())
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "simulated"
+    }
+}
+
+/// Reply of a healthy Bali DUT to `[init_status,]`, as captured on COM3
+/// (198 bytes, lines separated by CRLF).
+pub const BALI_INIT_STATUS: &str = "Aw:Init\r\n\
+Cw:Init\r\n\
+Bat:T\r\n\
+Fw0Version:03.01.02.04\r\n\
+Fw1Version:03.04.05\r\n\
+Model ID: 0\r\n\
+Model Name: Bali\r\n\
+PROD SN:25267359\r\n\
+BT:D01411205B83\r\n\
+BLE:D01411205B83\r\n\
+Calib:230\r\n\
+Mode0:NotDut\r\n\
+Mode fog: 0\r\n\
+TPF: 0";
+
+/// Behaviour of a [`SimulatedDut`].
+#[derive(Debug, Clone)]
+pub struct DutScript {
+    /// Delay between the power rising edge and the first boot log byte.
+    pub boot_delay: Duration,
+    /// Bytes emitted once after every cold boot.
+    pub boot_log: Vec<u8>,
+    /// Delay between a complete command frame and its reply.
+    pub response_delay: Duration,
+    /// Reply per command name; commands without an entry get no reply.
+    pub responses: HashMap<String, Vec<u8>>,
+    /// The DUT keeps running from its battery when POW/VCHARGER drop.
+    pub battery: bool,
+    /// After `[2700_shutdown,]` with 5V still present, the DUT is
+    /// re-activated and reboots once this much time has passed.
+    pub reactivate_after: Duration,
+    /// Host writes are read back on the UART, as on a single-wire link.
+    pub echo: bool,
+}
+
+impl Default for DutScript {
+    fn default() -> Self {
+        let mut responses = HashMap::new();
+        responses.insert(
+            "init_status".to_string(),
+            BALI_INIT_STATUS.as_bytes().to_vec(),
+        );
+
+        Self {
+            boot_delay: Duration::from_millis(500),
+            boot_log: b"Aw:Init\r\nCw:Init\r\n".to_vec(),
+            response_delay: Duration::from_millis(50),
+            responses,
+            battery: true,
+            reactivate_after: Duration::from_secs(2),
+            echo: false,
+        }
+    }
+}
+
+impl DutScript {
+    /// Sets (or replaces) the reply to the command `name`.
+    pub fn respond(mut self, name: &str, reply: &[u8]) -> Self {
+        self.responses.insert(name.to_string(), reply.to_vec());
+        self
+    }
+
+    /// Echoes host writes like the UART of `mode` does.
+    pub fn wire_mode(mut self, mode: WireMode) -> Self {
+        self.echo = mode.echoes();
+        self
+    }
+}
+
+/// Power state of a [`SimulatedDut`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DutPower {
+    /// No supply and no battery; the UART is silent.
+    Off,
+    /// Booted (or booting) and answering commands.
+    Running,
+    /// `[2700_shutdown,]` received; silent, waiting for the 5V to drop.
+    ShutdownPending,
+}
+
+struct DutState {
+    script: DutScript,
+    power: DutPower,
+    seen_events: usize,
+    /// Pin level after the last event replayed, the level at attach before
+    /// any.
+    pins: PinState,
+    shutdown_at: Duration,
+    rx: VecDeque<(Duration, Vec<u8>)>,
+    cmd_buf: Vec<u8>,
+    commands: Vec<String>,
+}
+
+impl DutState {
+    fn boot(&mut self, at: Duration) {
+        self.power = DutPower::Running;
+        self.rx.clear();
+        self.cmd_buf.clear();
+        if !self.script.boot_log.is_empty() {
+            let log = self.script.boot_log.clone();
+            self.rx.push_back((at + self.script.boot_delay, log));
+        }
+    }
+
+    fn power_down(&mut self) {
+        self.power = DutPower::Off;
+        self.rx.clear();
+        self.cmd_buf.clear();
+    }
+
+    fn check_reactivation(&mut self, now: Duration, powered: bool) {
+        let reactivate_at = self.shutdown_at + self.script.reactivate_after;
+        if self.power == DutPower::ShutdownPending && powered && now >= reactivate_at {
+            self.boot(reactivate_at);
+        }
+    }
+
+    fn handle_command(&mut self, frame: &[u8], now: Duration) {
+        let text = String::from_utf8_lossy(frame);
+        let body = text.trim_start_matches('[').trim_end_matches(']');
+        let name = body.split(',').next().unwrap_or("").trim().to_string();
+        self.commands.push(body.to_string());
+
+        if name == SHUTDOWN_COMMAND {
+            self.power = DutPower::ShutdownPending;
+            self.shutdown_at = now;
+            self.rx.clear();
+        } else if let Some(reply) = self.script.responses.get(&name) {
+            let reply = reply.clone();
+            self.rx.push_back((now + self.script.response_delay, reply));
+        }
+    }
+}
+
+/// Scriptable Bali DUT wired to one side of a [`SimulatedFixture`].
+///
+/// The DUT follows the POW/VCHARGER pins of its side: a rising edge from
+/// `Off` cold-boots it and emits the boot log, `[init_status,]` is answered
+/// while running, and after `[2700_shutdown,]` it goes silent and turns off
+/// once the supply is removed. Time is taken from the fixture clock and
+/// evaluated lazily whenever the DUT is accessed.
+#[derive(Clone)]
+pub struct SimulatedDut {
+    fixture: SimulatedFixture,
+    side: DeviceSide,
+    state: Arc<Mutex<DutState>>,
+}
+
+impl SimulatedDut {
+    /// Attaches a DUT to `side` of the fixture, which must be a single device.
+    pub fn new(fixture: &SimulatedFixture, side: DeviceSide, script: DutScript) -> Result<Self> {
+        if side == DeviceSide::Both {
+            return Err(PowerControllerError::InvalidDeviceSide);
+        }
+
+        // A DUT attached to an already powered slot is considered booted
+        let (pins, seen_events) = {
+            let st = fixture.lock();
+            (st.pins, st.history.len())
+        };
+        let power = if pins.is_supplied(side) {
+            DutPower::Running
+        } else {
+            DutPower::Off
+        };
+
+        Ok(Self {
+            fixture: fixture.clone(),
+            side,
+            state: Arc::new(Mutex::new(DutState {
+                script,
+                power,
+                seen_events,
+                pins,
+                shutdown_at: Duration::ZERO,
+                rx: VecDeque::new(),
+                cmd_buf: Vec::new(),
+                commands: Vec::new(),
+            })),
+        })
+    }
+
+    /// Creates the UART end of this DUT.
+    pub fn port(&self) -> SimDutPort {
+        SimDutPort {
+            dut: self.clone(),
+            timeout: Duration::from_millis(1000),
+        }
+    }
+
+    /// Current power state, after applying every pin change so far.
+    pub fn power(&self) -> DutPower {
+        self.update().power
+    }
+
+    /// Every command frame received while running, without brackets.
+    pub fn commands(&self) -> Vec<String> {
+        self.update().commands.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, DutState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Replays fixture pin changes the DUT has not seen yet and returns the
+    /// up-to-date state.
+    fn update(&self) -> MutexGuard<'_, DutState> {
+        let mut st = self.lock();
+        let events = self.fixture.events_from(st.seen_events);
+        st.seen_events += events.len();
+
+        for event in events {
+            // Reactivation is due while the level before this event held
+            let was_powered = st.pins.is_supplied(self.side);
+            st.check_reactivation(event.at, was_powered);
+            st.pins = event.state;
+            let powered = event.state.is_supplied(self.side);
+
+            match (st.power, powered) {
+                (DutPower::Off, true) => st.boot(event.at),
+                (DutPower::Running, false) if !st.script.battery => st.power_down(),
+                (DutPower::ShutdownPending, false) => st.power_down(),
+                _ => {}
+            }
+        }
+
+        let now = self.fixture.elapsed();
+        let powered = st.pins.is_supplied(self.side);
+        st.check_reactivation(now, powered);
+        st
+    }
+
+    fn read_available(&self, buf: &mut [u8]) -> usize {
+        let now = self.fixture.elapsed();
+        let mut st = self.update();
+        let mut n = 0;
+
+        while n < buf.len() {
+            let Some((at, chunk)) = st.rx.front_mut() else {
+                break;
+            };
+            if *at > now {
+                break;
+            }
+
+            let take = chunk.len().min(buf.len() - n);
+            buf[n..n + take].copy_from_slice(&chunk[..take]);
+            chunk.drain(..take);
+            n += take;
+            if chunk.is_empty() {
+                st.rx.pop_front();
+            }
+        }
+        n
+    }
+
+    fn receive(&self, data: &[u8]) {
+        let now = self.fixture.elapsed();
+        let mut st = self.update();
+        // The shared contact reads back the host even with the DUT off
+        if st.script.echo {
+            let index = st.rx.iter().take_while(|(at, _)| *at <= now).count();
+            st.rx.insert(index, (now, data.to_vec()));
+        }
+        if st.power != DutPower::Running {
+            return;
+        }
+
+        for &byte in data {
+            if byte == b'[' {
+                st.cmd_buf.clear();
+            }
+            st.cmd_buf.push(byte);
+            if byte == b']' {
+                let frame = std::mem::take(&mut st.cmd_buf);
+                st.handle_command(&frame, now);
+                if st.power != DutPower::Running {
+                    // Running from the battery: nothing re-activates it
+                    if !self.fixture.pins().is_supplied(self.side) {
+                        st.power_down();
+                    }
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// UART end of a [`SimulatedDut`], standing in for COM3/COM4.
+pub struct SimDutPort {
+    dut: SimulatedDut,
+    timeout: Duration,
+}
+
+impl SimDutPort {
+    /// The DUT this port is wired to.
+    pub fn dut(&self) -> &SimulatedDut {
+        &self.dut
+    }
+}
+
+impl Read for SimDutPort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let n = self.dut.read_available(buf);
+            if n > 0 {
+                return Ok(n);
+            }
+            if Instant::now() >= deadline {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "Operation timed out",
+                ));
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+impl Write for SimDutPort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.dut.receive(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl PinTransport for SimDutPort {
+    fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "simulated DUT"
+    }
+}
@@ -0,0 +1,314 @@
+use crate::dut::DUT_BAUD_RATE;
+use crate::error::{PowerControllerError, Result};
+use crate::power::PowerController;
+use crate::soft_uart::{IdlePolarity, SoftUartConfig, SoftUartDecoder, DB0_MASK};
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read, Write};
+use std::time::{Duration, Instant};
+
+/// Pin, speed and echo handling of the single-wire link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SingleWireOptions {
+    /// Bit-bang pin wired to the DUT charge contact.
+    pub pin_mask: u8,
+    pub baud: u32,
+    /// Bit-bang clock of the transport; `D2xxTransport::open` runs at
+    /// 62500 baud, i.e. 1M samples per second.
+    pub sample_rate: u32,
+    /// Idle bit times sent before each write so the DUT sees a clean edge.
+    pub lead_in_bits: u32,
+    /// Drop our own frames when they are read back from the shared wire.
+    pub discard_echo: bool,
+    /// How long a read waits for a frame. The pins are sampled
+    /// continuously, so a silent line still delivers samples.
+    pub timeout: Duration,
+}
+
+impl Default for SingleWireOptions {
+    fn default() -> Self {
+        Self {
+            pin_mask: DB0_MASK,
+            baud: DUT_BAUD_RATE,
+            sample_rate: 1_000_000,
+            lead_in_bits: 2,
+            discard_echo: true,
+            timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Half-duplex 8N1 UART on one bit-bang pin of the power-control port.
+///
+/// Writes drive the pin with an oversampled frame stream while the other
+/// pins keep the controller's rail state; afterwards the pin is switched to
+/// input and reads decode the sampled line. Dropping the link makes every
+/// pin an output again and rewrites the rail state.
+pub struct SingleWire<'a> {
+    controller: &'a mut PowerController,
+    options: SingleWireOptions,
+    decoder: SoftUartDecoder,
+    received: VecDeque<u8>,
+    echo: VecDeque<u8>,
+}
+
+impl PowerController {
+    /// Turns the link pin into a single-wire UART, starting in receive mode.
+    pub fn single_wire(&mut self, options: SingleWireOptions) -> Result<SingleWire<'_>> {
+        self.port_mut().set_pin_directions(!options.pin_mask)?;
+
+        let decoder = SoftUartDecoder::new(SoftUartConfig {
+            baud: options.baud,
+            pin_mask: options.pin_mask,
+            idle: IdlePolarity::High,
+            ..SoftUartConfig::new(options.sample_rate)
+        });
+
+        Ok(SingleWire {
+            controller: self,
+            options,
+            decoder,
+            received: VecDeque::new(),
+            echo: VecDeque::new(),
+        })
+    }
+}
+
+impl SingleWire<'_> {
+    pub fn options(&self) -> &SingleWireOptions {
+        &self.options
+    }
+
+    /// Changes how long a read waits for a frame; also used as the read
+    /// timeout of the port.
+    pub fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.controller.port_mut().set_timeout(timeout)?;
+        self.options.timeout = timeout;
+        Ok(())
+    }
+
+    /// Sample stream for `data`: lead-in, then start bit, 8 data bits LSB
+    /// first and a stop bit per byte. Pins outside the link keep the rail
+    /// state.
+    pub fn encode(&self, data: &[u8]) -> Vec<u8> {
+        let mask = self.options.pin_mask;
+        let space = self.controller.state().bits() & !mask;
+        let mark = space | mask;
+        let samples_per_bit = self.options.sample_rate as f64 / self.options.baud as f64;
+
+        let mut levels = vec![true; self.options.lead_in_bits as usize];
+        for byte in data {
+            levels.push(false);
+            levels.extend((0..8).map(|bit| byte >> bit & 1 == 1));
+            levels.push(true);
+        }
+
+        // Accumulate fractional bit lengths so long writes do not drift
+        let mut samples = Vec::with_capacity((levels.len() as f64 * samples_per_bit) as usize + 1);
+        for (i, level) in levels.iter().enumerate() {
+            let end = ((i + 1) as f64 * samples_per_bit).round() as usize;
+            samples.resize(end, if *level { mark } else { space });
+        }
+        samples
+    }
+
+    fn transmit(&mut self, data: &[u8]) -> Result<()> {
+        let samples = self.encode(data);
+        let port = self.controller.port_mut();
+
+        port.set_pin_directions(0xFF)?;
+        port.write_all(&samples)?;
+        // The write returns once the driver has the data; wait until the
+        // last stop bit is on the wire before releasing the pin
+        std::thread::sleep(Duration::from_secs_f64(
+            samples.len() as f64 / self.options.sample_rate as f64,
+        ));
+        port.set_pin_directions(!self.options.pin_mask)?;
+
+        if self.options.discard_echo {
+            self.echo.extend(data);
+        }
+        Ok(())
+    }
+
+    /// Decodes one batch of samples, returning how many were read.
+    fn receive(&mut self) -> io::Result<usize> {
+        let mut samples = [0u8; 4096];
+        let n = match self.controller.port_mut().read(&mut samples) {
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::TimedOut => 0,
+            Err(e) => return Err(e),
+        };
+
+        for byte in self.decoder.feed(&samples[..n]) {
+            if self.echo.front() == Some(&byte) {
+                self.echo.pop_front();
+                continue;
+            }
+            self.echo.clear();
+            self.received.push_back(byte);
+        }
+        Ok(n)
+    }
+}
+
+fn to_io(e: PowerControllerError) -> io::Error {
+    match e {
+        PowerControllerError::IoError(e) => e,
+        other => io::Error::other(other),
+    }
+}
+
+impl Read for SingleWire<'_> {
+    /// Returns decoded bytes, or `TimedOut` if no frame other than our
+    /// echo was decoded within the timeout or the port stopped delivering
+    /// samples.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let deadline = Instant::now() + self.options.timeout;
+        while self.received.is_empty() {
+            if self.receive()? == 0 || Instant::now() >= deadline {
+                return Err(ErrorKind::TimedOut.into());
+            }
+        }
+
+        let n = buf.len().min(self.received.len());
+        for (slot, byte) in buf.iter_mut().zip(self.received.drain(..n)) {
+            *slot = byte;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for SingleWire<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.transmit(buf).map_err(to_io)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for SingleWire<'_> {
+    fn drop(&mut self) {
+        let _ = self.controller.port_mut().set_pin_directions(0xFF);
+        let state = self.controller.state();
+        let _ = self.controller.set_state(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pins::PinState;
+    use crate::power::DeviceSide;
+    use crate::transport::PinTransport;
+    use std::sync::{Arc, Mutex};
+
+    /// Bit-bang port whose DB0 is a wire shared with a DUT: writes are read
+    /// back, and without traffic the pins are still sampled at idle.
+    #[derive(Clone, Default)]
+    struct Wire {
+        samples: Arc<Mutex<VecDeque<u8>>>,
+        idle: Arc<Mutex<u8>>,
+    }
+
+    impl Read for Wire {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let mut samples = self.samples.lock().unwrap();
+            let n = buf.len().min(256);
+            for slot in &mut buf[..n] {
+                *slot = samples.pop_front().unwrap_or(*self.idle.lock().unwrap());
+            }
+            Ok(n)
+        }
+    }
+
+    impl Write for Wire {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let state = *buf.last().unwrap_or(&0);
+            *self.idle.lock().unwrap() = state | DB0_MASK;
+            if buf.len() > 7 {
+                self.samples.lock().unwrap().extend(buf);
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl PinTransport for Wire {
+        fn set_pin_directions(&mut self, _: u8) -> Result<()> {
+            Ok(())
+        }
+
+        fn set_timeout(&mut self, _: Duration) -> Result<()> {
+            Ok(())
+        }
+
+        fn name(&self) -> &str {
+            "wire"
+        }
+    }
+
+    fn options() -> SingleWireOptions {
+        SingleWireOptions {
+            sample_rate: 96_000,
+            timeout: Duration::from_millis(50),
+            ..SingleWireOptions::default()
+        }
+    }
+
+    fn controller(wire: &Wire) -> PowerController {
+        let mut controller = PowerController::with_transport(Box::new(wire.clone())).unwrap();
+        controller
+            .set_state(PinState::supply(DeviceSide::Both) | PinState::from(0xC0))
+            .unwrap();
+        controller
+    }
+
+    #[test]
+    fn encoded_frames_decode_and_keep_the_rails() {
+        let wire = Wire::default();
+        let mut controller = controller(&wire);
+        let rails = controller.state().bits() & !DB0_MASK;
+        let link = controller.single_wire(options()).unwrap();
+
+        let data = b"[init_status,]\x00\xFF";
+        let samples = link.encode(data);
+        assert!(samples.iter().all(|s| s & !DB0_MASK == rails));
+
+        let config = SoftUartConfig {
+            idle: IdlePolarity::High,
+            ..SoftUartConfig::new(96_000)
+        };
+        let mut decoder = SoftUartDecoder::new(config);
+        let mut decoded = decoder.feed(&samples);
+        decoded.extend(decoder.feed(&[rails | DB0_MASK; 20]));
+        assert_eq!(decoded, data);
+        assert_eq!(decoder.framing_errors(), 0);
+    }
+
+    #[test]
+    fn echo_is_discarded_and_silence_times_out() {
+        let wire = Wire::default();
+        let mut controller = controller(&wire);
+        let mut link = controller.single_wire(options()).unwrap();
+
+        link.write_all(b"[ping,]").unwrap();
+        let reply = link.encode(b"pong");
+        wire.samples.lock().unwrap().extend(reply);
+
+        let mut reply = [0u8; 4];
+        link.read_exact(&mut reply).unwrap();
+        assert_eq!(&reply, b"pong");
+
+        // Idle samples keep arriving; the read still gives up in time
+        let started = Instant::now();
+        let err = link.read(&mut [0u8; 16]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,325 @@
+use crate::dut::DUT_BAUD_RATE;
+
+/// DB0 (TXD of the power-control interface), where the DUT single wire is
+/// sampled when the interface runs in bit-bang mode with mask 0xFE.
+pub const DB0_MASK: u8 = 0x01;
+
+/// Idle level of the sampled line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IdlePolarity {
+    /// Learn it from the line: the level that holds for a whole frame.
+    #[default]
+    Auto,
+    /// Standard UART, idle high, start bit low.
+    High,
+    /// Inverted line, idle low, start bit high.
+    Low,
+}
+
+/// Soft-UART settings for 8N1 frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SoftUartConfig {
+    /// Bit-bang samples per second, as actually delivered by the FTDI.
+    pub sample_rate: u32,
+    pub baud: u32,
+    /// Bit(s) of each sample byte that carry the line.
+    pub pin_mask: u8,
+    pub idle: IdlePolarity,
+    /// Level changes shorter than this many samples are treated as
+    /// glitches and ignored. 1 disables filtering.
+    pub glitch_samples: u32,
+}
+
+impl SoftUartConfig {
+    /// DB0 at the DUT baud rate, auto idle polarity and a glitch filter of
+    /// a quarter bit.
+    pub fn new(sample_rate: u32) -> Self {
+        let mut config = Self {
+            sample_rate,
+            baud: DUT_BAUD_RATE,
+            pin_mask: DB0_MASK,
+            idle: IdlePolarity::Auto,
+            glitch_samples: 1,
+        };
+        config.glitch_samples = (config.samples_per_bit() / 4.0).max(1.0) as u32;
+        config
+    }
+
+    pub fn samples_per_bit(&self) -> f64 {
+        self.sample_rate as f64 / self.baud as f64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frame {
+    /// Waiting for a start edge.
+    Idle,
+    /// Receiving a frame whose start edge was at this sample.
+    Receiving { start: u64, bit: u8, data: u8 },
+}
+
+/// Streaming decoder turning raw bit-bang samples into UART bytes.
+///
+/// Samples may be fed in chunks of any size; frames spanning two chunks
+/// are decoded once the rest arrives.
+#[derive(Debug, Clone)]
+pub struct SoftUartDecoder {
+    config: SoftUartConfig,
+    /// Index of the next sample.
+    position: u64,
+    /// Debounced line level.
+    level: bool,
+    /// Consecutive samples disagreeing with `level`.
+    pending: u32,
+    /// Sample at which `level` last changed.
+    last_edge: u64,
+    /// End of the previous frame; only edges after it can start a frame.
+    frame_end: u64,
+    /// Idle line level, once known.
+    idle: Option<bool>,
+    frame: Frame,
+    framing_errors: usize,
+}
+
+impl SoftUartDecoder {
+    pub fn new(config: SoftUartConfig) -> Self {
+        let idle = match config.idle {
+            IdlePolarity::Auto => None,
+            IdlePolarity::High => Some(true),
+            IdlePolarity::Low => Some(false),
+        };
+        Self {
+            config,
+            position: 0,
+            level: idle.unwrap_or(true),
+            pending: 0,
+            last_edge: 0,
+            frame_end: 0,
+            idle,
+            frame: Frame::Idle,
+            framing_errors: 0,
+        }
+    }
+
+    pub fn config(&self) -> &SoftUartConfig {
+        &self.config
+    }
+
+    /// Idle level in use, `None` while auto detection is still waiting for
+    /// a quiet frame.
+    pub fn idle_level(&self) -> Option<bool> {
+        self.idle
+    }
+
+    /// Frames whose stop bit was not at the idle level.
+    pub fn framing_errors(&self) -> usize {
+        self.framing_errors
+    }
+
+    /// Decodes a chunk of samples and returns the completed bytes.
+    pub fn feed(&mut self, samples: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for &sample in samples {
+            if let Some(byte) = self.step(sample & self.config.pin_mask != 0) {
+                bytes.push(byte);
+            }
+            self.position += 1;
+        }
+        bytes
+    }
+
+    fn step(&mut self, raw: bool) -> Option<u8> {
+        let glitch = self.config.glitch_samples.max(1);
+        if raw != self.level {
+            self.pending += 1;
+            if self.pending >= glitch {
+                self.level = raw;
+                // Back-date the edge to where the new level started
+                self.last_edge = self.position + 1 - self.pending as u64;
+                self.pending = 0;
+            }
+        } else {
+            self.pending = 0;
+        }
+
+        let spb = self.config.samples_per_bit();
+        let Some(idle) = self.idle else {
+            // Ten quiet bit times (one full frame) reveal the idle level
+            if (self.position - self.last_edge) as f64 >= spb * 10.0 {
+                self.idle = Some(self.level);
+            }
+            return None;
+        };
+
+        match self.frame {
+            Frame::Idle => {
+                if self.level != idle && self.last_edge >= self.frame_end {
+                    self.frame = Frame::Receiving {
+                        start: self.last_edge,
+                        bit: 0,
+                        data: 0,
+                    };
+                }
+                None
+            }
+            Frame::Receiving { start, bit, data } => {
+                // The debounced level lags the line by the glitch window
+                let center = start as f64 + (bit as f64 + 0.5) * spb + (glitch - 1) as f64;
+                if (self.position as f64) < center {
+                    return None;
+                }
+
+                let mark = self.level == idle;
+                match bit {
+                    // Start bit gone already: noise, not a frame
+                    0 if mark => {
+                        self.frame = Frame::Idle;
+                        None
+                    }
+                    0..=8 => {
+                        let data = if bit == 0 {
+                            data
+                        } else {
+                            data | (u8::from(mark) << (bit - 1))
+                        };
+                        self.frame = Frame::Receiving {
+                            start,
+                            bit: bit + 1,
+                            data,
+                        };
+                        None
+                    }
+                    _ => {
+                        self.frame = Frame::Idle;
+                        self.frame_end = self.position;
+                        if mark {
+                            Some(data)
+                        } else {
+                            self.framing_errors += 1;
+                            None
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Decodes a complete capture in one go.
+pub fn decode_samples(samples: &[u8], config: SoftUartConfig) -> Vec<u8> {
+    SoftUartDecoder::new(config).feed(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 10 samples per bit at 9600 baud.
+    const SAMPLE_RATE: u32 = 96_000;
+    const SPB: usize = 10;
+    /// Rail pins that are high in every sample and must be masked out.
+    const RAILS: u8 = 0xC0;
+
+    fn level(high: bool) -> u8 {
+        RAILS | u8::from(high)
+    }
+
+    /// Samples of `bytes` as 8N1 frames after `idle_bits` of idle line.
+    fn encode(bytes: &[u8], idle_bits: usize, idle: bool) -> Vec<u8> {
+        let mut samples = vec![level(idle); idle_bits * SPB];
+        for &byte in bytes {
+            let bits = std::iter::once(!idle)
+                .chain((0..8).map(|bit| (byte >> bit & 1 == 1) == idle))
+                .chain(std::iter::once(idle));
+            for bit in bits {
+                samples.extend(std::iter::repeat_n(level(bit), SPB));
+            }
+        }
+        samples.extend(std::iter::repeat_n(level(idle), 2 * SPB));
+        samples
+    }
+
+    fn config(idle: IdlePolarity) -> SoftUartConfig {
+        SoftUartConfig {
+            idle,
+            ..SoftUartConfig::new(SAMPLE_RATE)
+        }
+    }
+
+    #[test]
+    fn decodes_frames_on_db0() {
+        let samples = encode(b"Aw:Init\r\n", 2, true);
+        assert_eq!(
+            decode_samples(&samples, config(IdlePolarity::High)),
+            b"Aw:Init\r\n"
+        );
+    }
+
+    #[test]
+    fn frames_split_across_chunks() {
+        let samples = encode(b"[init_status,]", 2, true);
+        let mut decoder = SoftUartDecoder::new(config(IdlePolarity::High));
+
+        let mut decoded = Vec::new();
+        for chunk in samples.chunks(7) {
+            decoded.extend(decoder.feed(chunk));
+        }
+        assert_eq!(decoded, b"[init_status,]");
+        assert_eq!(decoder.framing_errors(), 0);
+    }
+
+    #[test]
+    fn auto_idle_waits_for_a_quiet_frame() {
+        let mut decoder = SoftUartDecoder::new(config(IdlePolarity::Auto));
+        assert_eq!(decoder.idle_level(), None);
+
+        // Less than a frame of idle line: nothing can be decided yet
+        decoder.feed(&[level(true); 5 * SPB]);
+        assert_eq!(decoder.idle_level(), None);
+
+        decoder.feed(&[level(true); 6 * SPB]);
+        assert_eq!(decoder.idle_level(), Some(true));
+        assert_eq!(decoder.feed(&encode(b"OK", 0, true)), b"OK");
+    }
+
+    #[test]
+    fn auto_idle_detects_an_inverted_line() {
+        let samples = encode(b"Cw:Init", 12, false);
+        let mut decoder = SoftUartDecoder::new(config(IdlePolarity::Auto));
+
+        assert_eq!(decoder.feed(&samples), b"Cw:Init");
+        assert_eq!(decoder.idle_level(), Some(false));
+    }
+
+    #[test]
+    fn glitch_filter_ignores_short_spikes() {
+        let mut samples = encode(b"\x55\xAA", 2, true);
+        // One-sample spikes on the idle line and at the centre of data bits,
+        // where an unfiltered decoder samples
+        samples[5] = level(false);
+        samples[2 * SPB + 3 * SPB + SPB / 2] ^= DB0_MASK;
+        samples[2 * SPB + 16 * SPB + SPB / 2] ^= DB0_MASK;
+
+        let filtered = config(IdlePolarity::High);
+        assert!(filtered.glitch_samples > 1);
+        assert_eq!(decode_samples(&samples, filtered), b"\x55\xAA");
+
+        let unfiltered = SoftUartConfig {
+            glitch_samples: 1,
+            ..filtered
+        };
+        assert_ne!(decode_samples(&samples, unfiltered), b"\x55\xAA");
+    }
+
+    #[test]
+    fn counts_framing_errors() {
+        let mut samples = encode(b"A", 2, true);
+        // Pull the stop bit low
+        let stop = 2 * SPB + 9 * SPB;
+        samples[stop..stop + SPB].fill(level(false));
+
+        let mut decoder = SoftUartDecoder::new(config(IdlePolarity::High));
+        assert!(decoder.feed(&samples).is_empty());
+        assert_eq!(decoder.framing_errors(), 1);
+    }
+}
@@ -1,3 +1,5 @@
+// DeviceSide and WireMode are used by the commented-out Tauri commands below
+#[allow(unused_imports)]
 use crate::power::{DeviceSide, PowerController, WireMode};
 use std::sync::Mutex;
 
@@ -19,7 +21,8 @@ pub struct PowerState {
 //         init_device,
 //         power_on_cmd,
 //         power_off_cmd,
-//         reset_device_cmd
+//         reset_device_cmd,
+//         pin_state_cmd
 //     ])
 //     .run(tauri::generate_context!())
 //     .expect("error while running tauri application");
@@ -82,6 +85,17 @@ pub fn reset_device_cmd(state: tauri::State<'_, PowerState>, side: String) -> Re
     }
 }
 
+// Returns the commanded pins, e.g. "DUT1[POW,VCHG] DUT2[-]"
+#[tauri::command]
+pub fn pin_state_cmd(state: tauri::State<'_, PowerState>) -> Result<String, String> {
+    let managed = state.controller.lock().unwrap();
+    if let Some(ref controller) = *managed {
+        Ok(controller.state().to_string())
+    } else {
+        Err("Device not initialized".into())
+    }
+}
+
 // Internal Helper
 fn parse_side(side_str: &str) -> Result<DeviceSide, String> {
     match side_str.to_lowercase().as_str() {
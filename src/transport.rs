@@ -0,0 +1,222 @@
+use crate::error::{PowerControllerError, Result};
+use libftd2xx::{BitMode, Ftdi, FtdiCommon};
+use serialport::SerialPort;
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+/// Description of FT4232H Port A, the interface wired to the power pins.
+pub const PRELUDE_POWER_DESCRIPTION: &str = "FT4232H_Orka Prelude A";
+
+/// Byte-level link to the GPIO adapter that drives the Prelude power pins.
+///
+/// `PowerController` only ever needs to push the 7-byte state payload and
+/// optionally read bytes back, so any backend that can do that (VCP serial
+/// port, native D2XX bit-bang handle, ...) can be selected at runtime by
+/// boxing it as `Box<dyn PinTransport>`.
+pub trait PinTransport: Read + Write + Send {
+    /// Writes one complete state payload to the adapter and flushes it.
+    fn write_payload(&mut self, payload: &[u8]) -> Result<()> {
+        self.write_all(payload)
+            .map_err(PowerControllerError::IoError)?;
+
+        // Optional flush
+        let _ = self.flush();
+
+        Ok(())
+    }
+
+    /// Samples the current level of the GPIO pins.
+    /// Backends that cannot observe the pins return `PinReadUnsupported`.
+    fn read_pins(&mut self) -> Result<u8> {
+        Err(PowerControllerError::PinReadUnsupported(
+            self.name().to_string(),
+        ))
+    }
+
+    /// Selects which GPIO pins are driven (bit set) and which are inputs.
+    /// Backends without bit-bang mode return `PinDirectionUnsupported`.
+    fn set_pin_directions(&mut self, outputs: u8) -> Result<()> {
+        let _ = outputs;
+        Err(PowerControllerError::PinDirectionUnsupported(
+            self.name().to_string(),
+        ))
+    }
+
+    /// Changes the read timeout used by `Read::read`.
+    fn set_timeout(&mut self, timeout: Duration) -> Result<()>;
+
+    /// Short human readable name of the backend, used in logs and errors.
+    fn name(&self) -> &str;
+}
+
+/// Transport over a virtual COM port opened through the `serialport` crate.
+pub struct SerialTransport {
+    name: String,
+    port: Box<dyn SerialPort>,
+}
+
+impl SerialTransport {
+    /// Opens `port_name` as 8N1 without flow control at the given baud rate.
+    pub fn open(port_name: &str, baud_rate: u32) -> Result<Self> {
+        let port = serialport::new(port_name, baud_rate)
+            .timeout(Duration::from_millis(5000))
+            .data_bits(serialport::DataBits::Eight)
+            .parity(serialport::Parity::None) // Assuming default 8N1
+            .stop_bits(serialport::StopBits::One)
+            .flow_control(serialport::FlowControl::None)
+            .open()
+            .map_err(|e| PowerControllerError::PortOpenError(port_name.to_string(), e))?;
+
+        Ok(Self::from_port(port))
+    }
+
+    /// Wraps an already opened serial port.
+    pub fn from_port(port: Box<dyn SerialPort>) -> Self {
+        let name = port.name().unwrap_or_else(|| "serial".to_string());
+        Self { name, port }
+    }
+
+    /// Access to the underlying serial port for backend specific settings
+    pub fn port_mut(&mut self) -> &mut Box<dyn SerialPort> {
+        &mut self.port
+    }
+}
+
+impl Read for SerialTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.port.read(buf)
+    }
+}
+
+impl Write for SerialTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.port.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.port.flush()
+    }
+}
+
+impl PinTransport for SerialTransport {
+    fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.port
+            .set_timeout(timeout)
+            .map_err(|e| PowerControllerError::ConfigError(self.name.clone(), e))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Transport over a native FTDI D2XX handle in asynchronous bit-bang mode.
+///
+/// In bit-bang mode every byte written sets the 8 GPIO pins of the
+/// interface directly, and reads return sampled pin levels.
+pub struct D2xxTransport {
+    name: String,
+    ft: Ftdi,
+    write_timeout: Duration,
+}
+
+impl D2xxTransport {
+    /// Opens the interface with the given description and configures it
+    /// exactly like the original C++ PreludeController:
+    /// USB transfer 4096, no event chars, 5s timeouts, latency 16ms,
+    /// no flow control, baud 62500 (bit rate x16 = 1M) and
+    /// asynchronous bit-bang with all pins as outputs.
+    pub fn open(description: &str) -> Result<Self> {
+        Self::configure(description, Ftdi::with_description(description)?)
+    }
+
+    /// Same as `open`, selecting the interface by its D2XX serial number
+    /// (e.g. `FT66ORKAA`), which stays unique with several fixtures attached.
+    pub fn open_serial(serial_number: &str) -> Result<Self> {
+        Self::configure(serial_number, Ftdi::with_serial_number(serial_number)?)
+    }
+
+    fn configure(name: &str, mut ft: Ftdi) -> Result<Self> {
+        let write_timeout = Duration::from_millis(5000);
+        ft.set_usb_parameters(4096)?;
+        ft.set_chars(0, false, 0, false)?;
+        ft.set_timeouts(Duration::from_millis(5000), write_timeout)?;
+        ft.set_latency_timer(Duration::from_millis(16))?;
+        ft.set_flow_control_none()?;
+        ft.set_baud_rate(62500)?;
+        ft.set_bit_mode(0xFF, BitMode::AsyncBitbang)?;
+
+        Ok(Self {
+            name: name.to_string(),
+            ft,
+            write_timeout,
+        })
+    }
+
+    /// Wraps an already opened and configured D2XX handle.
+    pub fn from_ftdi(name: &str, ft: Ftdi) -> Self {
+        Self {
+            name: name.to_string(),
+            ft,
+            write_timeout: Duration::from_millis(5000),
+        }
+    }
+
+    /// Access to the underlying D2XX handle for backend specific settings
+    pub fn ftdi_mut(&mut self) -> &mut Ftdi {
+        &mut self.ft
+    }
+}
+
+impl Read for D2xxTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.ft.read(buf).map_err(io::Error::other)
+    }
+}
+
+impl Write for D2xxTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.ft.write(buf).map_err(io::Error::other)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // D2XX writes are handed to the driver immediately
+        Ok(())
+    }
+}
+
+/// Payload as sent in bit-bang mode, where every byte drives all 8 pins:
+/// the preamble is replaced by the state byte so the pins never take any
+/// other level.
+pub(crate) fn bitbang_payload(payload: &[u8]) -> Vec<u8> {
+    match payload.last() {
+        Some(&state) => vec![state; payload.len()],
+        None => Vec::new(),
+    }
+}
+
+impl PinTransport for D2xxTransport {
+    fn write_payload(&mut self, payload: &[u8]) -> Result<()> {
+        self.write_all(&bitbang_payload(payload))?;
+        Ok(())
+    }
+
+    fn read_pins(&mut self) -> Result<u8> {
+        // FT_GetBitMode returns the instantaneous value of the data bus
+        Ok(self.ft.bit_mode()?)
+    }
+
+    fn set_pin_directions(&mut self, outputs: u8) -> Result<()> {
+        self.ft.set_bit_mode(outputs, BitMode::AsyncBitbang)?;
+        Ok(())
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.ft.set_timeouts(timeout, self.write_timeout)?;
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
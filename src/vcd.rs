@@ -0,0 +1,219 @@
+//! Value Change Dump export of raw bit-bang samples, so the single-wire
+//! waveform on DB0 can be opened in PulseView, GTKWave or other logic
+//! analyzer software.
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// In asynchronous bit-bang mode the FTDI clocks the pins at 16 times the
+/// configured baud rate (62500 baud gives 1M samples per second).
+pub const BITBANG_CLOCK_MULTIPLIER: u32 = 16;
+
+/// Sample rate of a bit-bang interface configured with `set_baud_rate(baud)`.
+pub fn bitbang_sample_rate(baud: u32) -> u32 {
+    baud * BITBANG_CLOCK_MULTIPLIER
+}
+
+/// What to put in the dump.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VcdOptions {
+    /// Samples per second of the capture.
+    pub sample_rate: u32,
+    /// Pins written as channels, bit 0 = DB0.
+    pub pins: u8,
+    /// Channel name per pin, DB0 first.
+    pub names: [String; 8],
+}
+
+impl VcdOptions {
+    /// All eight pins of a capture taken at `set_baud_rate(baud)`.
+    pub fn for_baud(baud: u32) -> Self {
+        Self {
+            sample_rate: bitbang_sample_rate(baud),
+            pins: 0xFF,
+            names: std::array::from_fn(|bit| format!("DB{bit}")),
+        }
+    }
+}
+
+/// Streams samples into a VCD file; only level changes are written, so
+/// long idle stretches cost nothing. Timestamps are in nanoseconds.
+pub struct VcdWriter<W: Write> {
+    writer: W,
+    options: VcdOptions,
+    /// Index of the next sample.
+    position: u64,
+    /// Level of the last sample written, `None` before the first one.
+    last: Option<u8>,
+}
+
+impl VcdWriter<BufWriter<File>> {
+    /// Creates (or truncates) the file at `path`.
+    pub fn create(path: impl AsRef<Path>, options: VcdOptions) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), options)
+    }
+}
+
+impl<W: Write> VcdWriter<W> {
+    /// Writes the header declaring one wire per selected pin.
+    pub fn new(mut writer: W, options: VcdOptions) -> io::Result<Self> {
+        if options.sample_rate == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "sample rate must not be zero",
+            ));
+        }
+
+        writeln!(
+            writer,
+            "$comment FTDI bit-bang capture at {} samples/s $end",
+            options.sample_rate
+        )?;
+        writeln!(writer, "$timescale 1 ns $end")?;
+        writeln!(writer, "$scope module bitbang $end")?;
+        for bit in selected(options.pins) {
+            writeln!(
+                writer,
+                "$var wire 1 {} {} $end",
+                identifier(bit),
+                options.names[bit as usize]
+            )?;
+        }
+        writeln!(writer, "$upscope $end")?;
+        writeln!(writer, "$enddefinitions $end")?;
+
+        Ok(Self {
+            writer,
+            options,
+            position: 0,
+            last: None,
+        })
+    }
+
+    /// Appends consecutive samples.
+    pub fn write_samples(&mut self, samples: &[u8]) -> io::Result<()> {
+        let mask = self.options.pins;
+        for &sample in samples {
+            let changed = match self.last {
+                None => mask,
+                Some(last) => (last ^ sample) & mask,
+            };
+            if changed != 0 {
+                writeln!(self.writer, "#{}", self.timestamp(self.position))?;
+                if self.last.is_none() {
+                    writeln!(self.writer, "$dumpvars")?;
+                }
+                for bit in selected(changed) {
+                    writeln!(self.writer, "{}{}", sample >> bit & 1, identifier(bit))?;
+                }
+                if self.last.is_none() {
+                    writeln!(self.writer, "$end")?;
+                }
+                self.last = Some(sample);
+            }
+            self.position += 1;
+        }
+        Ok(())
+    }
+
+    /// Marks the end of the capture so the last level keeps its length,
+    /// and returns the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        writeln!(self.writer, "#{}", self.timestamp(self.position))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn timestamp(&self, sample: u64) -> u64 {
+        (sample as u128 * 1_000_000_000 / self.options.sample_rate as u128) as u64
+    }
+}
+
+/// Writes a complete capture to `path` in one go.
+pub fn export_vcd(path: impl AsRef<Path>, samples: &[u8], options: VcdOptions) -> io::Result<()> {
+    let mut vcd = VcdWriter::create(path, options)?;
+    vcd.write_samples(samples)?;
+    vcd.finish()?;
+    Ok(())
+}
+
+fn selected(pins: u8) -> impl Iterator<Item = u8> {
+    (0..8).filter(move |bit| pins >> bit & 1 == 1)
+}
+
+/// Short VCD identifier of a pin: `!` for DB0, `"` for DB1, ...
+fn identifier(bit: u8) -> char {
+    (b'!' + bit) as char
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> VcdOptions {
+        let mut options = VcdOptions {
+            sample_rate: 1_000_000,
+            pins: 0b0000_0101,
+            ..VcdOptions::for_baud(62_500)
+        };
+        options.names[0] = "SWIO".to_string();
+        options
+    }
+
+    #[test]
+    fn header_declares_only_the_selected_pins() {
+        let vcd = VcdWriter::new(Vec::new(), options()).unwrap();
+        let text = String::from_utf8(vcd.finish().unwrap()).unwrap();
+
+        let vars: Vec<_> = text.lines().filter(|l| l.starts_with("$var")).collect();
+        assert_eq!(vars, ["$var wire 1 ! SWIO $end", "$var wire 1 # DB2 $end"]);
+        assert!(text.contains("$timescale 1 ns $end"));
+        assert!(text.ends_with("$enddefinitions $end\n#0\n"));
+    }
+
+    #[test]
+    fn only_changes_follow_the_initial_dump() {
+        let mut vcd = VcdWriter::new(Vec::new(), options()).unwrap();
+        // DB1 is not selected, so the third sample is no change
+        vcd.write_samples(&[0x00, 0x00, 0x02]).unwrap();
+        vcd.write_samples(&[0x01, 0x01, 0x05, 0x04]).unwrap();
+        let text = String::from_utf8(vcd.finish().unwrap()).unwrap();
+
+        let body = text.split("$enddefinitions $end\n").nth(1).unwrap();
+        assert_eq!(
+            body,
+            "#0\n$dumpvars\n0!\n0#\n$end\n\
+             #3000\n1!\n\
+             #5000\n1#\n\
+             #6000\n0!\n\
+             #7000\n"
+        );
+    }
+
+    #[test]
+    fn timestamps_follow_the_sample_rate() {
+        let options = VcdOptions {
+            sample_rate: 3,
+            pins: 0x01,
+            ..VcdOptions::for_baud(9600)
+        };
+        let mut vcd = VcdWriter::new(Vec::new(), options).unwrap();
+        vcd.write_samples(&[0, 1, 0, 0]).unwrap();
+        let text = String::from_utf8(vcd.finish().unwrap()).unwrap();
+
+        let stamps: Vec<_> = text.lines().filter(|l| l.starts_with('#')).collect();
+        assert_eq!(stamps, ["#0", "#333333333", "#666666666", "#1333333333"]);
+    }
+
+    #[test]
+    fn zero_sample_rate_is_rejected() {
+        let options = VcdOptions {
+            sample_rate: 0,
+            ..VcdOptions::for_baud(9600)
+        };
+        let mut out = Vec::new();
+        let err = VcdWriter::new(&mut out, options).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(out.is_empty());
+    }
+}
@@ -0,0 +1,207 @@
+use crate::error::{PowerControllerError, Result};
+use crate::fixture::Fixture;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Change in the set of attached Prelude boards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FixtureEvent {
+    /// A board appeared, or its port mapping changed after re-enumeration.
+    Attached(Fixture),
+    /// A board disappeared; carries the mapping it had while attached.
+    Detached(Fixture),
+}
+
+impl FixtureEvent {
+    pub fn fixture(&self) -> &Fixture {
+        match self {
+            FixtureEvent::Attached(fixture) | FixtureEvent::Detached(fixture) => fixture,
+        }
+    }
+
+    pub fn serial(&self) -> &str {
+        &self.fixture().serial
+    }
+}
+
+/// Polls the attached boards in a background thread and reports changes.
+///
+/// Boards already present when the watcher starts are reported as
+/// `Attached` on the first poll. The thread stops when the watcher is
+/// dropped.
+pub struct FixtureWatcher {
+    events: Receiver<FixtureEvent>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl FixtureWatcher {
+    /// Watches with `Fixture::rediscover`, polling every `interval`
+    /// (`hotplug_capture.rs` used 500ms). Interfaces opened meanwhile keep
+    /// the mapping of the previous poll.
+    pub fn spawn(interval: Duration) -> Self {
+        let mut previous = Vec::new();
+        Self::with_source(interval, move || {
+            let fixtures = Fixture::rediscover(&previous)?;
+            previous.clone_from(&fixtures);
+            Ok(fixtures)
+        })
+    }
+
+    /// Watches the fixtures returned by `source`; a poll that fails is
+    /// skipped rather than reported as a detach.
+    pub fn with_source<F>(interval: Duration, source: F) -> Self
+    where
+        F: FnMut() -> Result<Vec<Fixture>> + Send + 'static,
+    {
+        let (tx, events) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = stop.clone();
+            std::thread::spawn(move || poll(interval, source, tx, stop))
+        };
+
+        Self {
+            events,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Channel carrying the events, for `recv`, `try_iter` or `select`-style use.
+    pub fn events(&self) -> &Receiver<FixtureEvent> {
+        &self.events
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<FixtureEvent> {
+        self.events.recv_timeout(timeout).map_err(|e| match e {
+            RecvTimeoutError::Timeout => PowerControllerError::Timeout,
+            RecvTimeoutError::Disconnected => PowerControllerError::WatcherStopped,
+        })
+    }
+
+    /// Waits for a board to attach, any board if `serial` is `None`.
+    /// Other events received meanwhile are dropped.
+    pub fn wait_attached(&self, serial: Option<&str>, timeout: Duration) -> Result<Fixture> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if let FixtureEvent::Attached(fixture) = self.recv_timeout(remaining)? {
+                if serial.is_none_or(|s| s == fixture.serial) {
+                    return Ok(fixture);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for FixtureWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn poll<F>(interval: Duration, mut source: F, tx: Sender<FixtureEvent>, stop: Arc<AtomicBool>)
+where
+    F: FnMut() -> Result<Vec<Fixture>>,
+{
+    let mut known: Vec<Fixture> = Vec::new();
+
+    while !stop.load(Ordering::Relaxed) {
+        if let Ok(current) = source() {
+            // Boards are identified by serial; a board that stays attached
+            // with a new mapping is only reported as `Attached` again
+            for old in &known {
+                if !current.iter().any(|f| f.serial == old.serial) {
+                    let _ = tx.send(FixtureEvent::Detached(old.clone()));
+                }
+            }
+            for new in &current {
+                if !known.contains(new) {
+                    let _ = tx.send(FixtureEvent::Attached(new.clone()));
+                }
+            }
+            known = current;
+        }
+
+        // Sleep in small slices so dropping the watcher does not block long
+        let wake = Instant::now() + interval;
+        while !stop.load(Ordering::Relaxed) && Instant::now() < wake {
+            std::thread::sleep(interval.min(Duration::from_millis(20)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libftd2xx::DeviceInfo;
+    use std::collections::VecDeque;
+
+    fn fixture(serial: &str, interfaces: &str) -> Fixture {
+        let devices: Vec<_> = interfaces
+            .chars()
+            .map(|letter| DeviceInfo {
+                serial_number: format!("{serial}{letter}"),
+                description: format!("FT4232H_Orka Prelude {letter}"),
+                ..DeviceInfo::default()
+            })
+            .collect();
+        Fixture::from_listings(&devices, &[]).remove(0)
+    }
+
+    /// Events of a watcher fed the given polls, one per interval.
+    fn events(polls: Vec<Vec<Fixture>>) -> Vec<FixtureEvent> {
+        let count = polls.len();
+        let mut polls = VecDeque::from(polls);
+        let watcher = FixtureWatcher::with_source(Duration::from_millis(1), move || {
+            Ok(polls.pop_front().unwrap_or_default())
+        });
+
+        let mut events = Vec::new();
+        while let Ok(event) = watcher.recv_timeout(Duration::from_millis(200)) {
+            events.push(event);
+            if events.len() > count * 2 {
+                break;
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn reports_attach_and_detach() {
+        let a = fixture("FT66ORKA", "ACD");
+        let events = events(vec![vec![a.clone()], vec![]]);
+        assert_eq!(
+            events,
+            vec![FixtureEvent::Attached(a.clone()), FixtureEvent::Detached(a)]
+        );
+    }
+
+    #[test]
+    fn changed_mapping_is_not_a_detach() {
+        let before = fixture("FT66ORKA", "ACD");
+        let after = fixture("FT66ORKA", "CD");
+        let events = events(vec![
+            vec![before.clone()],
+            vec![after.clone()],
+            vec![after.clone()],
+        ]);
+        assert_eq!(
+            events[..2],
+            [
+                FixtureEvent::Attached(before),
+                FixtureEvent::Attached(after)
+            ]
+        );
+        assert!(events[2..]
+            .iter()
+            .all(|e| matches!(e, FixtureEvent::Detached(_))));
+    }
+}
@@ -0,0 +1,510 @@
+use prelude_power_controller::sim::BALI_INIT_STATUS;
+use prelude_power_controller::{
+    expect_any_line, expect_line, BootLogCapture, Capture, DeviceSide, DutClient, DutOptions,
+    DutPower, DutScript, LogStream, LogStreamOptions, Pattern, PinState, PinTransport,
+    PowerController, PowerControllerError, PowerSequence, Recorder, ShutdownPolicy, SimulatedDut,
+    SimulatedFixture, WireMode, SHUTDOWN_COMMAND,
+};
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A fixture with a running DUT on side 1 whose UART behaves like `mode`.
+fn running_dut(mode: WireMode, script: DutScript) -> SimulatedDut {
+    running_dut_on(&SimulatedFixture::new(), mode, script).1
+}
+
+fn running_dut_on(
+    fixture: &SimulatedFixture,
+    mode: WireMode,
+    script: DutScript,
+) -> (PowerController, SimulatedDut) {
+    let script = DutScript {
+        boot_delay: Duration::ZERO,
+        boot_log: Vec::new(),
+        response_delay: Duration::from_millis(5),
+        ..script
+    }
+    .wire_mode(mode);
+    let dut = SimulatedDut::new(fixture, DeviceSide::Device1, script).unwrap();
+
+    let mut controller = PowerController::with_transport(Box::new(fixture.transport())).unwrap();
+    controller.enable_vcharger(DeviceSide::Device1).unwrap();
+    controller.power_on(DeviceSide::Device1).unwrap();
+    (controller, dut)
+}
+
+fn client(dut: &SimulatedDut, options: DutOptions) -> DutClient {
+    let options = DutOptions {
+        response_timeout: Duration::from_millis(500),
+        idle_gap: Duration::from_millis(50),
+        poll_interval: Duration::from_millis(5),
+        ..options
+    };
+    DutClient::with_transport(Box::new(dut.port()), options).unwrap()
+}
+
+#[test]
+fn single_wire_echo_is_stripped() {
+    let dut = running_dut(WireMode::SingleWire, DutScript::default());
+    let mut client = client(&dut, DutOptions::for_mode(WireMode::SingleWire));
+
+    let response = client.command("init_status", &[]).unwrap();
+    assert_eq!(response.raw, BALI_INIT_STATUS.as_bytes());
+    assert_eq!(dut.commands(), vec!["init_status,"]);
+}
+
+#[test]
+fn single_wire_echo_reaches_a_client_without_stripping() {
+    let dut = running_dut(WireMode::SingleWire, DutScript::default());
+    let mut client = client(&dut, DutOptions::for_mode(WireMode::DoubleWire));
+
+    let response = client.command("init_status", &[]).unwrap();
+    let mut expected = b"[init_status,]".to_vec();
+    expected.extend_from_slice(BALI_INIT_STATUS.as_bytes());
+    assert_eq!(response.raw, expected);
+}
+
+#[test]
+fn single_wire_echo_is_stripped_while_the_dut_is_off() {
+    let fixture = SimulatedFixture::new();
+    let script = DutScript::default().wire_mode(WireMode::SingleWire);
+    let dut = SimulatedDut::new(&fixture, DeviceSide::Device1, script).unwrap();
+    let mut client = client(&dut, DutOptions::for_mode(WireMode::SingleWire));
+
+    client.write_raw(b"[init_status,]").unwrap();
+    assert!(client.receive().is_err());
+    assert!(dut.commands().is_empty());
+}
+
+#[test]
+fn double_wire_replies_arrive_unchanged() {
+    let dut = running_dut(WireMode::DoubleWire, DutScript::default());
+    let mut client = client(&dut, DutOptions::for_mode(WireMode::DoubleWire));
+
+    let response = client.command("init_status", &[]).unwrap();
+    assert_eq!(response.raw, BALI_INIT_STATUS.as_bytes());
+}
+
+#[test]
+fn double_wire_keeps_replies_that_repeat_the_command() {
+    let script = DutScript::default().respond("ping", b"[ping,]pong\r\n");
+    let dut = running_dut(WireMode::DoubleWire, script);
+    let mut client = client(&dut, DutOptions::for_mode(WireMode::DoubleWire));
+
+    let response = client.command("ping", &[]).unwrap();
+    assert_eq!(response.raw, b"[ping,]pong\r\n");
+}
+
+#[test]
+fn reactivation_is_replayed_from_the_level_before_unseen_events() {
+    let fixture = SimulatedFixture::new();
+    let script = DutScript {
+        reactivate_after: Duration::from_millis(50),
+        ..DutScript::default()
+    };
+    let (mut controller, dut) = running_dut_on(&fixture, WireMode::DoubleWire, script);
+    let mut client = client(&dut, DutOptions::for_mode(WireMode::DoubleWire));
+
+    client.send("2700_shutdown", &[]).unwrap();
+    assert_eq!(dut.power(), DutPower::ShutdownPending);
+
+    // 5V stays on past the reactivation time, then drops before the DUT
+    // is looked at again; it rebooted in between and now runs on battery
+    std::thread::sleep(Duration::from_millis(100));
+    controller
+        .apply(|pins| pins.remove(PinState::supply(DeviceSide::Device1)))
+        .unwrap();
+    assert_eq!(dut.power(), DutPower::Running);
+}
+
+#[test]
+fn expect_runs_on_a_log_stream_next_to_other_subscribers() {
+    let fixture = SimulatedFixture::new();
+    let script = DutScript {
+        boot_delay: Duration::from_millis(20),
+        ..DutScript::default()
+    };
+    let dut = SimulatedDut::new(&fixture, DeviceSide::Device1, script).unwrap();
+    let stream = LogStream::spawn(
+        vec![(DeviceSide::Device1, Box::new(dut.port()))],
+        LogStreamOptions::default(),
+    );
+    let matcher = stream.subscribe();
+    let viewer = stream.subscribe();
+
+    let mut controller = PowerController::with_transport(Box::new(fixture.transport())).unwrap();
+    controller.power_on(DeviceSide::Device1).unwrap();
+
+    let patterns = [
+        Pattern::literal("Boot failed"),
+        Pattern::regex(r"^C(\w):(\w+)$").unwrap(),
+    ];
+    let found = expect_any_line(&matcher, &patterns, Duration::from_secs(2)).unwrap();
+    assert_eq!(found.pattern, 1);
+    assert_eq!(found.text, "Cw:Init");
+    assert_eq!(
+        found.groups,
+        [Some("w".to_string()), Some("Init".to_string())]
+    );
+    assert_eq!(found.line.dut, DeviceSide::Device1);
+
+    let seen: Vec<_> = viewer.iter().take(2).map(|line| line.text).collect();
+    assert_eq!(seen, ["Aw:Init", "Cw:Init"]);
+
+    assert!(matches!(
+        expect_line(&matcher, "Aw:Init", Duration::from_millis(50)),
+        Err(PowerControllerError::Timeout)
+    ));
+    drop(stream);
+    assert!(matches!(
+        expect_line(&matcher, "Aw:Init", Duration::from_millis(50)),
+        Err(PowerControllerError::LogStreamStopped)
+    ));
+}
+
+/// A DUT on side 1 that prints its boot log 20ms after power-on, with a
+/// client listening before the supplies rise.
+fn booting_dut(fixture: &SimulatedFixture) -> (PowerController, SimulatedDut, DutClient) {
+    let script = DutScript {
+        boot_delay: Duration::from_millis(20),
+        response_delay: Duration::from_millis(5),
+        ..DutScript::default()
+    };
+    let dut = SimulatedDut::new(fixture, DeviceSide::Device1, script).unwrap();
+    let client = client(&dut, DutOptions::default());
+    let mut controller = PowerController::with_transport(Box::new(fixture.transport())).unwrap();
+    controller.enable_vcharger(DeviceSide::Device1).unwrap();
+    controller.power_on(DeviceSide::Device1).unwrap();
+    (controller, dut, client)
+}
+
+#[test]
+fn expect_waits_for_the_boot_banner_before_commands() {
+    let fixture = SimulatedFixture::new();
+    let (_controller, dut, mut client) = booting_dut(&fixture);
+
+    let banner = client.expect("Aw:Init", Duration::from_secs(1)).unwrap();
+    assert_eq!(banner.pattern, 0);
+    assert_eq!(banner.text, "Aw:Init");
+    assert_eq!(banner.before, "");
+
+    let info = client.init_status().unwrap();
+    assert_eq!(info.model_name.as_deref(), Some("Bali"));
+    assert_eq!(dut.commands(), vec!["init_status,"]);
+}
+
+#[test]
+fn output_after_a_match_stays_buffered() {
+    let fixture = SimulatedFixture::new();
+    let (_controller, _dut, mut client) = booting_dut(&fixture);
+
+    client.expect("Aw:Init", Duration::from_secs(1)).unwrap();
+    let rest = client.receive().unwrap();
+    assert_eq!(rest.text, "\r\nCw:Init\r\n");
+}
+
+#[test]
+fn expect_times_out_and_keeps_what_it_read() {
+    let fixture = SimulatedFixture::new();
+    let (_controller, _dut, mut client) = booting_dut(&fixture);
+
+    let patterns = [Pattern::literal("Bat:F"), Pattern::literal("Bat:T")];
+    assert!(matches!(
+        client.expect_any(&patterns, Duration::from_millis(200)),
+        Err(PowerControllerError::Timeout)
+    ));
+
+    let cw = client.expect("Cw:Init", Duration::ZERO).unwrap();
+    assert_eq!(cw.before, "Aw:Init\r\n");
+}
+
+#[test]
+fn expect_skips_the_single_wire_echo() {
+    let dut = running_dut(WireMode::SingleWire, DutScript::default());
+    let mut client = client(&dut, DutOptions::for_mode(WireMode::SingleWire));
+
+    client.send("init_status", &[]).unwrap();
+    let anchored = Pattern::regex(r"^Aw:Init\r\n").unwrap();
+    let reply = client.expect(anchored, Duration::from_secs(1)).unwrap();
+    assert_eq!(reply.before, "");
+
+    let version = Pattern::regex(r"Fw0Version:(\S+)").unwrap();
+    let version = client.expect(version, Duration::from_secs(1)).unwrap();
+    assert_eq!(version.groups, vec![Some("03.01.02.04".to_string())]);
+    assert!(!version.before.contains("init_status"));
+}
+
+#[test]
+fn boot_log_keeps_output_sent_on_vcharger_alone() {
+    let fixture = SimulatedFixture::new();
+    let script = DutScript {
+        boot_delay: Duration::from_millis(20),
+        ..DutScript::default()
+    };
+    let dut = SimulatedDut::new(&fixture, DeviceSide::Device1, script).unwrap();
+    let mut controller = PowerController::with_transport(Box::new(fixture.transport())).unwrap();
+
+    let capture = BootLogCapture {
+        listen: Duration::from_millis(100),
+        off_time: Duration::from_millis(10),
+        vcharger_lead: Duration::from_millis(150),
+        ..BootLogCapture::default()
+    };
+    let logs = capture
+        .capture(
+            &mut controller,
+            vec![(DeviceSide::Device1, Box::new(dut.port()))],
+        )
+        .unwrap();
+
+    let log = &logs[0];
+    assert!(log.vcharger_lead >= capture.vcharger_lead);
+    assert!(!log.before_pow.is_empty());
+    assert!(log.before_pow.iter().all(|c| c.at <= log.vcharger_lead));
+    assert!(log.before_pow.windows(2).all(|w| w[0].at >= w[1].at));
+    assert!(log.chunks.is_empty() && log.lines.is_empty());
+    assert_eq!(log.first_byte, None);
+    assert_eq!(log.text(), "Aw:Init\r\nCw:Init\r\n");
+    assert!(fixture.pins().pow1() && fixture.pins().vcharger1());
+}
+
+#[test]
+fn boot_log_times_output_from_the_pow_edge() {
+    let fixture = SimulatedFixture::new();
+    let script = DutScript {
+        boot_delay: Duration::from_millis(100),
+        ..DutScript::default()
+    };
+    let dut = SimulatedDut::new(&fixture, DeviceSide::Device1, script).unwrap();
+    let mut controller = PowerController::with_transport(Box::new(fixture.transport())).unwrap();
+
+    let capture = BootLogCapture {
+        listen: Duration::from_millis(200),
+        off_time: Duration::from_millis(10),
+        vcharger_lead: Duration::from_millis(20),
+        ..BootLogCapture::default()
+    };
+    let logs = capture
+        .capture(
+            &mut controller,
+            vec![(DeviceSide::Device1, Box::new(dut.port()))],
+        )
+        .unwrap();
+
+    // The DUT boots off the VCHARGER edge, so its output arrives
+    // boot_delay - vcharger_lead after POW
+    let log = &logs[0];
+    assert!(log.before_pow.is_empty());
+    let first_byte = log.first_byte.unwrap();
+    assert!(first_byte + log.vcharger_lead >= Duration::from_millis(100));
+    assert!(first_byte < Duration::from_millis(100));
+    assert_eq!(log.lines[0].at, first_byte);
+    assert_eq!(log.lines[0].text, "Aw:Init");
+    assert_eq!(log.total_bytes, log.raw().len());
+}
+
+fn quick_shutdown() -> ShutdownPolicy {
+    ShutdownPolicy {
+        command_delay: Duration::from_millis(20),
+        rail_gap: Duration::from_millis(10),
+        verify_deadline: Duration::from_millis(500),
+        probe_timeout: Duration::from_millis(100),
+        ..ShutdownPolicy::default()
+    }
+}
+
+#[test]
+fn shutdown_sequence_and_controller_take_the_dut_down_alike() {
+    let policy = quick_shutdown();
+
+    let fixture = SimulatedFixture::new();
+    let (mut controller, dut) =
+        running_dut_on(&fixture, WireMode::DoubleWire, DutScript::default());
+    let mut link = client(&dut, DutOptions::for_mode(WireMode::DoubleWire));
+    controller
+        .run_sequence_with(
+            DeviceSide::Device1,
+            &PowerSequence::shutdown(&policy),
+            Some(&mut link),
+        )
+        .unwrap();
+    let by_sequence = fixture.history().last().unwrap().state;
+    assert_eq!(dut.power(), DutPower::Off);
+    assert_eq!(dut.commands(), [format!("{SHUTDOWN_COMMAND},")]);
+
+    let fixture = SimulatedFixture::new();
+    let (mut controller, dut) =
+        running_dut_on(&fixture, WireMode::DoubleWire, DutScript::default());
+    let mut client = client(&dut, DutOptions::for_mode(WireMode::DoubleWire));
+    let report = controller
+        .shutdown(DeviceSide::Device1, &mut client, &policy)
+        .unwrap();
+    assert!(report.is_success());
+    assert_eq!(dut.power(), DutPower::Off);
+    assert_eq!(dut.commands(), [format!("{SHUTDOWN_COMMAND},")]);
+    assert_eq!(fixture.history().last().unwrap().state, by_sequence);
+}
+
+#[test]
+fn sequence_waits_past_the_single_wire_echo() {
+    let (mut controller, dut) = running_dut_on(
+        &SimulatedFixture::new(),
+        WireMode::SingleWire,
+        DutScript::default(),
+    );
+    let mut client = client(&dut, DutOptions::for_mode(WireMode::SingleWire));
+    let poll_interval = client.options().poll_interval;
+
+    // Only the echo carries the command name
+    let echo_only = PowerSequence::new()
+        .send(b"[init_status,]")
+        .wait_for("init_status", Duration::from_millis(200));
+    let result = controller.run_sequence_with(DeviceSide::Device1, &echo_only, Some(&mut client));
+    assert!(matches!(result, Err(PowerControllerError::Timeout)));
+
+    let reply = PowerSequence::new()
+        .drain()
+        .send(b"[init_status,]")
+        .wait_for("Fw0Version:", Duration::from_millis(500));
+    controller
+        .run_sequence_with(DeviceSide::Device1, &reply, Some(&mut client))
+        .unwrap();
+    let rest = client.expect("TPF: 0", Duration::from_millis(500)).unwrap();
+    assert!(rest.before.starts_with("03.01.02.04"));
+    assert_eq!(client.options().poll_interval, poll_interval);
+}
+
+/// DUT port that never stops logging.
+struct Streaming;
+
+impl Read for Streaming {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let line = b"Aw:log\n";
+        let n = buf.len().min(line.len());
+        buf[..n].copy_from_slice(&line[..n]);
+        Ok(n)
+    }
+}
+
+impl Write for Streaming {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl PinTransport for Streaming {
+    fn set_timeout(&mut self, _: Duration) -> prelude_power_controller::Result<()> {
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "streaming"
+    }
+}
+
+#[test]
+fn clearing_a_streaming_dut_stops_at_the_response_timeout() {
+    let options = DutOptions {
+        response_timeout: Duration::from_millis(100),
+        ..DutOptions::default()
+    };
+    let mut client = DutClient::with_transport(Box::new(Streaming), options).unwrap();
+
+    let started = Instant::now();
+    client.clear_input().unwrap();
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_millis(100));
+    assert!(elapsed < Duration::from_secs(2));
+}
+
+/// In-memory capture file shared with the recorder.
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn recorded_session_replays_on_a_fresh_fixture() {
+    let fixture = SimulatedFixture::new();
+    let script = DutScript {
+        boot_delay: Duration::ZERO,
+        boot_log: Vec::new(),
+        response_delay: Duration::from_millis(5),
+        ..DutScript::default()
+    }
+    .wire_mode(WireMode::DoubleWire);
+    let dut = SimulatedDut::new(&fixture, DeviceSide::Device1, script).unwrap();
+
+    let buffer = Buffer::default();
+    let recorder = Recorder::new(buffer.clone()).unwrap();
+    assert!(recorder
+        .dut_transport(DeviceSide::Both, Box::new(dut.port()))
+        .is_err());
+
+    let power = recorder.power_transport(Box::new(fixture.transport()));
+    let mut controller = PowerController::with_transport(power).unwrap();
+    controller.enable_vcharger(DeviceSide::Device1).unwrap();
+    controller.power_on(DeviceSide::Device1).unwrap();
+    let uart = recorder
+        .dut_transport(DeviceSide::Device1, Box::new(dut.port()))
+        .unwrap();
+    let options = DutOptions {
+        idle_gap: Duration::from_millis(50),
+        poll_interval: Duration::from_millis(5),
+        ..DutOptions::for_mode(WireMode::DoubleWire)
+    };
+    let mut client = DutClient::with_transport(uart, options).unwrap();
+    client.command("init_status", &[]).unwrap();
+    controller.power_off(DeviceSide::Device1).unwrap();
+
+    let recorded: Vec<u8> = fixture.history().iter().map(|e| e.state.bits()).collect();
+    let capture = Capture::read(&buffer.0.lock().unwrap()[..]).unwrap();
+    let written: Vec<u8> = capture.pin_writes().map(|(_, state)| state).collect();
+    assert_eq!(written, recorded);
+    assert_eq!(
+        capture.dut_bytes(DeviceSide::Device1, true),
+        b"[init_status,]"
+    );
+    assert_eq!(
+        capture.dut_bytes(DeviceSide::Device1, false),
+        BALI_INIT_STATUS.as_bytes()
+    );
+
+    let replayed = SimulatedFixture::new();
+    let replay = capture.replay(&replayed);
+    let mut port = replay.dut_port(DeviceSide::Device1);
+    port.write_all(b"[init_status,]").unwrap();
+    let mut received = Vec::new();
+    let mut buf = [0u8; 64];
+    while received.len() < BALI_INIT_STATUS.len() {
+        let n = port.read(&mut buf).unwrap();
+        received.extend_from_slice(&buf[..n]);
+    }
+    assert_eq!(received, BALI_INIT_STATUS.as_bytes());
+    assert_eq!(port.written(), b"[init_status,]");
+    port.set_timeout(Duration::from_millis(50)).unwrap();
+    assert!(port.read(&mut buf).is_err());
+
+    replay.wait();
+    let history = replayed.history();
+    let replayed_states: Vec<u8> = history.iter().map(|e| e.state.bits()).collect();
+    assert_eq!(replayed_states, recorded);
+    for ((at, _), event) in capture.pin_writes().zip(&history) {
+        assert!(event.at >= at);
+    }
+    assert_eq!(replayed.malformed_payloads(), 0);
+}
@@ -0,0 +1,276 @@
+use prelude_power_controller::{
+    ConnectOptions, DeviceSide, Fixture, FixtureRegistry, InitialState, PinState, PinTransport,
+    PowerController, PowerControllerError, PowerSequence, Rail, ResetOptions, SequenceStep,
+    SimTransport, SimulatedFixture,
+};
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+fn connect(fixture: &SimulatedFixture) -> PowerController {
+    PowerController::with_transport(Box::new(fixture.transport())).unwrap()
+}
+
+/// Write-only link to the fixture, like a VCP without pin read-back.
+struct WriteOnly(SimTransport);
+
+impl Read for WriteOnly {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for WriteOnly {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl PinTransport for WriteOnly {
+    fn write_payload(&mut self, payload: &[u8]) -> prelude_power_controller::Result<()> {
+        self.0.write_payload(payload)
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> prelude_power_controller::Result<()> {
+        self.0.set_timeout(timeout)
+    }
+
+    fn name(&self) -> &str {
+        "write-only"
+    }
+}
+
+#[test]
+fn connect_drives_every_rail_off() {
+    let fixture = SimulatedFixture::with_pins(PinState::all());
+    let controller = connect(&fixture);
+
+    let history = fixture.history();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].state, controller.state());
+    assert!(!fixture.pins().intersects(PinState::MANAGED));
+    assert_eq!(fixture.malformed_payloads(), 0);
+}
+
+#[test]
+fn power_on_and_off_follow_the_side() {
+    let fixture = SimulatedFixture::new();
+    let mut controller = connect(&fixture);
+    fixture.clear_history();
+
+    controller.power_on(DeviceSide::Device1).unwrap();
+    assert!(fixture.pins().pow1());
+    assert!(!fixture.pins().pow2());
+
+    controller.power_on(DeviceSide::Both).unwrap();
+    assert!(fixture.pins().pow1() && fixture.pins().pow2());
+
+    controller.power_off(DeviceSide::Device1).unwrap();
+    assert!(!fixture.pins().pow1());
+    assert!(fixture.pins().pow2());
+
+    let history: Vec<_> = fixture.history().iter().map(|e| e.state).collect();
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[2], controller.state());
+    assert_eq!(fixture.malformed_payloads(), 0);
+}
+
+#[test]
+fn reset_pulses_only_the_reset_pin() {
+    let fixture = SimulatedFixture::new();
+    let mut controller = connect(&fixture);
+    controller.power_on(DeviceSide::Device2).unwrap();
+    fixture.clear_history();
+
+    let options = ResetOptions {
+        width: Duration::from_millis(20),
+        ..ResetOptions::default()
+    };
+    controller.reset_with(DeviceSide::Device2, options).unwrap();
+
+    let history = fixture.history();
+    assert_eq!(history.len(), 2);
+    assert!(history[0].state.reset2());
+    assert!(!history[0].state.reset1());
+    assert!(history[0].state.pow2());
+    assert!(!history[1].state.reset2());
+    assert!(history[1].state.pow2());
+    assert!(history[1].at - history[0].at >= options.width);
+}
+
+#[test]
+fn active_low_reset_idles_high() {
+    let fixture = SimulatedFixture::new();
+    let mut controller = connect(&fixture);
+    fixture.clear_history();
+
+    let options = ResetOptions {
+        width: Duration::from_millis(5),
+        active_low: true,
+        ..ResetOptions::default()
+    };
+    controller.reset_with(DeviceSide::Device1, options).unwrap();
+
+    let history = fixture.history();
+    assert!(!history[0].state.reset1());
+    assert!(history[1].state.reset1());
+}
+
+#[test]
+fn every_write_is_recorded_in_order() {
+    let fixture = SimulatedFixture::new();
+    let mut controller = connect(&fixture);
+    fixture.clear_history();
+
+    controller.enable_vcharger(DeviceSide::Device1).unwrap();
+    controller.power_on(DeviceSide::Device1).unwrap();
+    controller.disable_vcharger(DeviceSide::Device1).unwrap();
+
+    let history = fixture.history();
+    let states: Vec<_> = history.iter().map(|e| e.state).collect();
+    assert_eq!(states.len(), 3);
+    assert!(states[0].vcharger1() && !states[0].pow1());
+    assert!(states[1].vcharger1() && states[1].pow1());
+    assert!(!states[2].vcharger1() && states[2].pow1());
+    assert!(history.windows(2).all(|w| w[0].at <= w[1].at));
+}
+
+#[test]
+fn sequence_reset_uses_the_controller_pulse() {
+    let fixture = SimulatedFixture::new();
+    let mut controller = connect(&fixture);
+    let options = ResetOptions {
+        width: Duration::from_millis(30),
+        active_low: true,
+        settle: Duration::from_millis(40),
+    };
+    controller.set_reset_options(options);
+    fixture.clear_history();
+
+    let started = std::time::Instant::now();
+    let seq = PowerSequence::new().enable(&[Rail::Pow]).reset();
+    controller.run_sequence(DeviceSide::Device1, &seq).unwrap();
+    assert!(started.elapsed() >= options.width + options.settle);
+
+    let history = fixture.history();
+    assert_eq!(history.len(), 3);
+    assert!(!history[1].state.reset1() && history[1].state.pow1());
+    assert!(history[2].state.reset1() && history[2].state.pow1());
+    assert!(history[2].at - history[1].at >= options.width);
+}
+
+#[test]
+fn warm_boot_pulses_reset_through_the_controller() {
+    let steps = PowerSequence::warm_boot();
+    assert!(steps.steps().contains(&SequenceStep::Reset));
+    assert!(!steps.steps().iter().any(|step| matches!(
+        step,
+        SequenceStep::Enable(rails) | SequenceStep::Disable(rails) if rails.contains(&Rail::Reset)
+    )));
+}
+
+#[test]
+fn verify_without_read_back_fails_before_writing() {
+    let fixture = SimulatedFixture::new();
+    let options = ConnectOptions {
+        verify_writes: true,
+        ..ConnectOptions::default()
+    };
+    let result =
+        PowerController::with_transport_options(Box::new(WriteOnly(fixture.transport())), options);
+
+    assert!(matches!(
+        result,
+        Err(PowerControllerError::PinReadUnsupported(_))
+    ));
+    assert!(fixture.history().is_empty());
+}
+
+#[test]
+fn preserve_without_read_back_fails_before_writing() {
+    let fixture = SimulatedFixture::new();
+    let options = ConnectOptions {
+        initial_state: InitialState::Preserve,
+        ..ConnectOptions::default()
+    };
+    let result =
+        PowerController::with_transport_options(Box::new(WriteOnly(fixture.transport())), options);
+
+    assert!(result.is_err());
+    assert!(fixture.history().is_empty());
+}
+
+#[test]
+fn enabling_verify_without_read_back_is_rejected() {
+    let fixture = SimulatedFixture::new();
+    let mut controller =
+        PowerController::with_transport(Box::new(WriteOnly(fixture.transport()))).unwrap();
+    fixture.clear_history();
+
+    assert!(controller.set_verify_writes(true).is_err());
+    controller.power_on(DeviceSide::Device1).unwrap();
+    assert_eq!(fixture.history().len(), 1);
+}
+
+#[test]
+fn verify_detects_a_stuck_pin() {
+    let fixture = SimulatedFixture::new();
+    let mut controller = connect(&fixture);
+    controller.set_verify_writes(true).unwrap();
+    fixture.stick_pins(PinState::pow(DeviceSide::Device1), PinState::empty());
+
+    assert!(matches!(
+        controller.power_on(DeviceSide::Device1),
+        Err(PowerControllerError::PinMismatch { .. })
+    ));
+}
+
+/// Registry listing one board, found through its power-control interface.
+fn registry(serial: &str) -> FixtureRegistry {
+    let devices = [libftd2xx::DeviceInfo {
+        serial_number: format!("{serial}A"),
+        description: "FT4232H_Orka Prelude A".to_string(),
+        ..libftd2xx::DeviceInfo::default()
+    }];
+    FixtureRegistry::from_fixtures(Fixture::from_listings(&devices, &[]))
+}
+
+#[test]
+fn registry_hands_a_board_out_once() {
+    let registry = registry("FTREGCLAIM");
+    let fixture = SimulatedFixture::new();
+    let connect = |_: &Fixture| PowerController::with_transport(Box::new(fixture.transport()));
+
+    let mut handle = registry.open_with("FTREGCLAIM", connect).unwrap();
+    assert_eq!(handle.serial(), "FTREGCLAIM");
+    assert!(FixtureRegistry::is_claimed("FTREGCLAIM"));
+    assert!(matches!(
+        registry.open_with("FTREGCLAIM", connect),
+        Err(PowerControllerError::FixtureInUse(serial)) if serial == "FTREGCLAIM"
+    ));
+    handle.power().power_on(DeviceSide::Device1).unwrap();
+    assert!(fixture.pins().pow1());
+
+    drop(handle);
+    assert!(!FixtureRegistry::is_claimed("FTREGCLAIM"));
+    assert!(registry.open_with("FTREGCLAIM", connect).is_ok());
+}
+
+#[test]
+fn registry_rejects_an_unknown_serial() {
+    let registry = registry("FTREGKNOWN");
+    let fixture = SimulatedFixture::new();
+    let result = registry.open_with("FTREGOTHER", |_| {
+        PowerController::with_transport(Box::new(fixture.transport()))
+    });
+
+    assert!(matches!(
+        result,
+        Err(PowerControllerError::FixtureNotFound(serial)) if serial == "FTREGOTHER"
+    ));
+    assert!(!FixtureRegistry::is_claimed("FTREGOTHER"));
+    assert!(fixture.history().is_empty());
+}